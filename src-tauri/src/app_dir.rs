@@ -0,0 +1,71 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use serde::Serialize;
+use tracing::warn;
+
+const IDENTIFIER: &str = "net.prgrssv.simple-bbs-writer";
+
+/// アプリの設定ファイルを置くディレクトリ
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|x| x.join(IDENTIFIER))
+}
+
+/// `value` を JSON で `path` に書き出す。書きかけで落ちても前の内容が残るように置き換える
+pub fn write_json_atomically<T: Serialize>(path: &Path, value: &T) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(value)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// 送られた内容を別のスレッドで JSON ファイルに書き出す。
+/// `delay` だけ新しいものが来なくなるのを待ち、一番新しいものだけを書き出す
+pub struct JsonSaver<T> {
+    sender: Option<mpsc::Sender<T>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl<T: Serialize + Send + 'static> JsonSaver<T> {
+    pub fn spawn(path: PathBuf, delay: Duration) -> Self {
+        let (sender, receiver) = mpsc::channel::<T>();
+        let handle = std::thread::spawn(move || {
+            while let Ok(mut value) = receiver.recv() {
+                while let Ok(newer) = receiver.recv_timeout(delay) {
+                    value = newer;
+                }
+                if let Err(err) = write_json_atomically(&path, &value) {
+                    warn!("failed to save {}: {:?}", path.display(), err);
+                }
+            }
+        });
+        Self {
+            sender: Some(sender),
+            handle: Some(handle),
+        }
+    }
+
+    pub fn save(&self, value: T) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(value);
+        }
+    }
+}
+
+impl<T> Drop for JsonSaver<T> {
+    /// 書き出しが終わるまで待つ
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{LazyLock, Mutex},
+};
+
+use encoding_rs::Encoding;
+
+use super::error::Result;
+
+/// 板の設定。値が無いものは制限なしとして扱う
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BoardSettings {
+    pub title: Option<String>,
+    pub comment: Option<String>,
+    pub noname_name: Option<String>,
+    pub max_message_bytes: Option<usize>,
+    pub max_lines: Option<usize>,
+    pub max_name_bytes: Option<usize>,
+    pub max_mail_bytes: Option<usize>,
+    pub max_subject_bytes: Option<usize>,
+    pub thread_stop: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    MessageTooLong { bytes: usize, max: usize },
+    TooManyLines { lines: usize, max: usize },
+    NameTooLong { bytes: usize, max: usize },
+    MailTooLong { bytes: usize, max: usize },
+    SubjectTooLong { bytes: usize, max: usize },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MessageTooLong { bytes, max } => {
+                write!(f, "本文が長すぎます ({} / {} バイト)", bytes, max)
+            }
+            Self::TooManyLines { lines, max } => {
+                write!(f, "本文の行数が多すぎます ({} / {} 行)", lines, max)
+            }
+            Self::NameTooLong { bytes, max } => {
+                write!(f, "名前が長すぎます ({} / {} バイト)", bytes, max)
+            }
+            Self::MailTooLong { bytes, max } => {
+                write!(f, "メール欄が長すぎます ({} / {} バイト)", bytes, max)
+            }
+            Self::SubjectTooLong { bytes, max } => {
+                write!(
+                    f,
+                    "スレッドタイトルが長すぎます ({} / {} バイト)",
+                    bytes, max
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// `KEY=VALUE` 形式の設定を読む
+pub fn parse_key_values(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(k, v)| (k.trim().to_owned(), v.trim().to_owned()))
+        .collect()
+}
+
+/// 2ch 互換の SETTING.TXT を読む
+pub fn parse_setting_txt(text: &str) -> BoardSettings {
+    let values = parse_key_values(text);
+    let string = |key: &str| values.get(key).filter(|x| !x.is_empty()).cloned();
+    let number = |key: &str| values.get(key).and_then(|x| x.parse::<usize>().ok());
+    BoardSettings {
+        title: string("BBS_TITLE"),
+        comment: string("BBS_COMMENT"),
+        noname_name: string("BBS_NONAME_NAME"),
+        max_message_bytes: number("BBS_MESSAGE_COUNT"),
+        // BBS_LINE_NUMBER は最大行数の半分
+        max_lines: number("BBS_LINE_NUMBER").map(|x| x * 2),
+        max_name_bytes: number("BBS_NAME_COUNT"),
+        max_mail_bytes: number("BBS_MAIL_COUNT"),
+        max_subject_bytes: number("BBS_SUBJECT_COUNT"),
+        thread_stop: values.get("BBS_THREAD_STOP").and_then(|x| x.parse().ok()),
+    }
+}
+
+pub fn encoded_len(encoding: &'static Encoding, text: &str) -> usize {
+    encoding.encode(text).0.len()
+}
+
+impl BoardSettings {
+    pub fn validate(
+        &self,
+        encoding: &'static Encoding,
+        name: &str,
+        mail: &str,
+        msg: &str,
+    ) -> Result<(), ValidationError> {
+        if let Some(max) = self.max_message_bytes {
+            let bytes = encoded_len(encoding, msg);
+            if bytes > max {
+                return Err(ValidationError::MessageTooLong { bytes, max });
+            }
+        }
+        if let Some(max) = self.max_lines {
+            let lines = msg.split('\n').count();
+            if lines > max {
+                return Err(ValidationError::TooManyLines { lines, max });
+            }
+        }
+        if let Some(max) = self.max_name_bytes {
+            let bytes = encoded_len(encoding, name);
+            if bytes > max {
+                return Err(ValidationError::NameTooLong { bytes, max });
+            }
+        }
+        if let Some(max) = self.max_mail_bytes {
+            let bytes = encoded_len(encoding, mail);
+            if bytes > max {
+                return Err(ValidationError::MailTooLong { bytes, max });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn validate_subject(
+        &self,
+        encoding: &'static Encoding,
+        subject: &str,
+    ) -> Result<(), ValidationError> {
+        if let Some(max) = self.max_subject_bytes {
+            let bytes = encoded_len(encoding, subject);
+            if bytes > max {
+                return Err(ValidationError::SubjectTooLong { bytes, max });
+            }
+        }
+        Ok(())
+    }
+}
+
+static CACHE: LazyLock<Mutex<HashMap<String, BoardSettings>>> = LazyLock::new(Default::default);
+
+/// 板ごとに一度だけ取得する
+pub async fn fetch_cached<F>(board_url: &str, fetch: F) -> Result<BoardSettings>
+where
+    F: Future<Output = Result<BoardSettings>>,
+{
+    if let Some(settings) = CACHE.lock().unwrap().get(board_url) {
+        return Ok(settings.clone());
+    }
+    let settings = fetch.await?;
+    CACHE
+        .lock()
+        .unwrap()
+        .insert(board_url.to_owned(), settings.clone());
+    Ok(settings)
+}
@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use reqwest::{IntoUrl, Proxy, RequestBuilder, Response, redirect};
+
+use super::{
+    COOKIE_JAR, UA,
+    error::{BbsError, Result},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BbsClientConfig {
+    pub user_agent: String,
+    pub connect_timeout: Duration,
+    pub read_timeout: Duration,
+    /// 追いかけるリダイレクトの回数。0 なら追いかけない
+    pub max_redirects: usize,
+    /// `http://host:port` や `socks5://host:port` のプロキシ
+    pub proxy: Option<String>,
+}
+
+impl Default for BbsClientConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: UA.to_owned(),
+            connect_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_secs(30),
+            max_redirects: 5,
+            proxy: None,
+        }
+    }
+}
+
+/// 掲示板との通信すべてで共有する HTTP クライアント
+#[derive(Clone)]
+pub struct BbsClient {
+    client: reqwest::Client,
+}
+
+impl BbsClient {
+    pub fn new(config: &BbsClientConfig) -> Result<Self> {
+        let redirect = match config.max_redirects {
+            0 => redirect::Policy::none(),
+            n => redirect::Policy::limited(n),
+        };
+        let mut builder = reqwest::Client::builder()
+            .cookie_provider(COOKIE_JAR.clone())
+            .user_agent(&config.user_agent)
+            .connect_timeout(config.connect_timeout)
+            .read_timeout(config.read_timeout)
+            .redirect(redirect);
+        if let Some(proxy) = &config.proxy {
+            let proxy = Proxy::all(proxy).map_err(|_| BbsError::InvalidProxy(proxy.clone()))?;
+            builder = builder.proxy(proxy);
+        }
+        Ok(Self {
+            client: builder.build()?,
+        })
+    }
+
+    pub fn get(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn post(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.client.post(url)
+    }
+}
+
+/// 2xx 以外の応答をエラーにする。`error_for_status` と違い、追いかけなかったリダイレクトもエラーにする
+pub trait CheckStatus: Sized {
+    fn check_status(self) -> Result<Self>;
+}
+
+impl CheckStatus for Response {
+    fn check_status(self) -> Result<Self> {
+        if self.status().is_success() {
+            return Ok(self);
+        }
+        Err(BbsError::HttpStatus {
+            status: self.status(),
+            url: self.url().to_string(),
+            empty_body: self.content_length() == Some(0),
+        })
+    }
+}
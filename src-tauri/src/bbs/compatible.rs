@@ -1,21 +1,41 @@
-use anyhow::{Result, anyhow, bail};
-use encoding_rs::Encoding;
-use percent_encoding::{NON_ALPHANUMERIC, percent_encode};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use encoding_rs::SHIFT_JIS;
 use regex::Regex;
-use reqwest::header::USER_AGENT;
+use tokio::sync::Mutex;
 use tracing::debug;
 use url::Url;
 
-use super::{Thread, UA};
+use super::{
+    BbsError, BbsProvider, Board, COOKIE_JAR, Capabilities, PostOutcome, Res, Thread, ThreadReader,
+    board_settings::{BoardSettings, fetch_cached, parse_setting_txt},
+    client::{BbsClient, CheckStatus},
+    dat::DatCache,
+    encoding::{charset_percent_encode, decode_response, encode_form, unmappable_policy},
+    error::{Result, encoding_for_label},
+    outcome::parse_post_response,
+    res::parse_dat,
+    subject::{ThreadSummary, find_thread_by_title, split_title_res_count},
+};
 
-pub fn parse_thread_url(thread_url: &Url) -> Option<Compatible> {
-    let origin = thread_url.origin().ascii_serialization();
+/// `/test/read.cgi/BBS/KEY/` を `(BBS, KEY)` にする
+fn parse_thread_path(thread_url: &Url) -> Option<(String, u64)> {
     let c = Regex::new(r"^/test/read.cgi/(.+?)/(.+?)(:?/.*)?$")
         .unwrap()
         .captures(thread_url.path())?;
-    let bbs = c.get(1).unwrap().as_str().to_string();
-    let key = c.get(2).unwrap().as_str().parse().ok()?;
-    Some(Compatible { origin, bbs, key })
+    Some((
+        c.get(1).unwrap().as_str().to_string(),
+        c.get(2).unwrap().as_str().parse().ok()?,
+    ))
+}
+
+pub fn parse_thread_url(client: &BbsClient, thread_url: &Url) -> Option<Compatible> {
+    let origin = thread_url.origin().ascii_serialization();
+    let (bbs, key) = parse_thread_path(thread_url)?;
+    Some(Compatible::new(
+        CompatibleBoard::new(client.clone(), origin, bbs),
+        key,
+    ))
 }
 
 pub fn parse_board_url(board_url: &Url) -> Option<String> {
@@ -25,78 +45,320 @@ pub fn parse_board_url(board_url: &Url) -> Option<String> {
     Some(c.get(1).unwrap().as_str().to_string())
 }
 
-async fn fetch_subject_txt(origin: &str, bbs: &str) -> Result<String> {
+fn board_url(origin: &str, bbs: &str) -> Url {
+    Url::parse(&format!("{}/{}/", origin, bbs)).unwrap()
+}
+
+async fn fetch_subject_txt(client: &BbsClient, origin: &str, bbs: &str) -> Result<String> {
     let subject_url = format!("{}/{}/subject.txt", origin, bbs);
-    Ok(reqwest::get(subject_url).await?.text().await?)
+    let resp = client.get(subject_url).send().await?.check_status()?;
+    decode_response(resp, SHIFT_JIS).await
 }
 
-fn read_latest_thread(subject_txt: &str) -> Result<u64> {
-    Ok(subject_txt
+/// `KEY.dat<>TITLE (N)` 形式の subject.txt を読む
+pub(super) fn read_thread_list(subject_txt: &str) -> Vec<ThreadSummary> {
+    subject_txt
         .lines()
-        .next()
-        .ok_or_else(|| anyhow!("Empty subject.txt"))?
-        .split(".")
-        .next()
-        .ok_or_else(|| anyhow!("Empty subject.txt"))?
-        .parse()?)
+        .filter_map(|line| {
+            let (file, rest) = line.split_once("<>")?;
+            let key = file.strip_suffix(".dat")?.parse().ok()?;
+            let (title, res_count) = split_title_res_count(rest)?;
+            Some(ThreadSummary {
+                key,
+                title,
+                res_count,
+            })
+        })
+        .collect()
 }
 
-async fn fetch_latest_thread(origin: &str, bbs: &str) -> Result<u64> {
-    let subject_txt = fetch_subject_txt(origin, bbs).await?;
-    read_latest_thread(&subject_txt)
+pub async fn fetch_thread_list(
+    client: &BbsClient,
+    origin: &str,
+    bbs: &str,
+) -> Result<Vec<ThreadSummary>> {
+    let subject_txt = fetch_subject_txt(client, origin, bbs).await?;
+    Ok(read_thread_list(&subject_txt))
 }
 
-pub async fn fetch_latest_thread_url(origin: &str, bbs: &str) -> Result<Url> {
-    let key = fetch_latest_thread(origin, bbs).await?;
-    let thread_url = format!("{}/test/read.cgi/{}/{}", origin, bbs, key);
-    Ok(Url::parse(&thread_url).unwrap())
+async fn fetch_setting_txt(client: &BbsClient, origin: &str, bbs: &str) -> Result<BoardSettings> {
+    let setting_url = format!("{}/{}/SETTING.TXT", origin, bbs);
+    let resp = client.get(setting_url).send().await?.check_status()?;
+    Ok(parse_setting_txt(&decode_response(resp, SHIFT_JIS).await?))
 }
 
-fn charset_percent_encode(encoding: &'static Encoding, text: &str) -> String {
-    let (text, _, _) = encoding.encode(text);
-    percent_encode(&text, NON_ALPHANUMERIC).to_string()
+pub async fn fetch_board_settings(
+    client: &BbsClient,
+    origin: &str,
+    bbs: &str,
+) -> Result<BoardSettings> {
+    let board_url = format!("{}/{}/", origin, bbs);
+    fetch_cached(&board_url, fetch_setting_txt(client, origin, bbs)).await
 }
 
-pub struct Compatible {
+/// 書き込み確認ページに含まれる hidden / submit の input を読む
+pub(super) fn read_confirmation_inputs(html: &str) -> Vec<(String, String)> {
+    let attr = Regex::new(r#"(?i)([a-z]+)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#).unwrap();
+    Regex::new(r"(?i)<input\s([^>]*)>")
+        .unwrap()
+        .captures_iter(html)
+        .filter_map(|input| {
+            let mut ty = None;
+            let mut name = None;
+            let mut value = String::new();
+            for c in attr.captures_iter(&input[1]) {
+                let v = c
+                    .get(2)
+                    .or_else(|| c.get(3))
+                    .or_else(|| c.get(4))
+                    .unwrap()
+                    .as_str();
+                match c[1].to_ascii_lowercase().as_str() {
+                    "type" => ty = Some(v.to_ascii_lowercase()),
+                    "name" => name = Some(v.to_owned()),
+                    "value" => value = v.to_owned(),
+                    _ => {}
+                }
+            }
+            match ty.as_deref() {
+                Some("hidden") | Some("submit") => Some((name?, value)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+pub struct CompatibleBoard {
+    client: BbsClient,
     origin: String,
     bbs: String,
-    key: u64,
 }
 
-impl Compatible {
-    pub async fn new(url: &Url) -> Result<Self> {
-        if let Some(site) = parse_thread_url(url) {
-            return Ok(site);
+impl CompatibleBoard {
+    pub fn new(client: BbsClient, origin: String, bbs: String) -> Self {
+        Self {
+            client,
+            origin,
+            bbs,
         }
-        bail!("Invalid URL: {}", url);
     }
-}
 
-#[async_trait::async_trait]
-impl Thread for Compatible {
-    async fn post(&self, charset: &str, name: &str, email: &str, msg: &str) -> Result<()> {
-        let encoding = Encoding::for_label(charset.as_bytes()).unwrap();
-        let name = charset_percent_encode(encoding, name);
-        let email = charset_percent_encode(encoding, email);
-        let msg = charset_percent_encode(encoding, msg);
-        let resp = reqwest::Client::new()
+    async fn send_form(&self, charset: &str, form: &[(String, String)]) -> Result<String> {
+        let encoding = encoding_for_label(charset)?;
+        let policy = unmappable_policy(&self.board_url());
+        let body = encode_form(encoding, &policy, form)?;
+        let resp = self
+            .client
             .post(format!("{}/test/bbs.cgi", self.origin))
-            .header(USER_AGENT, UA)
             .header(
                 "Content-Type",
                 format!("application/x-www-form-urlencoded; charset={}", charset),
             )
-            .header("Cookie", format!(r#"NAME="{}";MAIL="{}""#, name, email))
-            .body(format!(
-                "FROM={}&mail={}&MESSAGE={}&key={}&bbs={}",
-                name, email, msg, self.key, self.bbs
-            ))
+            .body(body)
             .send()
             .await?
-            .error_for_status()?;
-        let bytes = resp.bytes().await?;
-        let text = Encoding::for_label(b"shift_jis").unwrap().decode(&bytes).0;
-        debug!("post resp: {}", text.to_string());
-        Ok(())
+            .check_status()?;
+        let text = decode_response(resp, encoding).await?;
+        debug!("post resp: {}", text);
+        Ok(text)
+    }
+
+    /// bbs.cgi に送信する。書き込み確認ページが返ってきたら一度だけ再送する
+    async fn submit(
+        &self,
+        charset: &str,
+        mut form: Vec<(String, String)>,
+        name: &str,
+        email: &str,
+    ) -> Result<PostOutcome> {
+        let encoding = encoding_for_label(charset)?;
+        let policy = unmappable_policy(&self.board_url());
+        COOKIE_JAR.set(
+            &self.origin,
+            "NAME",
+            &format!(r#""{}""#, charset_percent_encode(encoding, name, &policy)?),
+        );
+        COOKIE_JAR.set(
+            &self.origin,
+            "MAIL",
+            &format!(r#""{}""#, charset_percent_encode(encoding, email, &policy)?),
+        );
+        let text = self.send_form(charset, &form).await?;
+        let outcome = parse_post_response(&text);
+        if outcome != PostOutcome::ConfirmationRequired {
+            return Ok(outcome);
+        }
+
+        // 書き込み確認: 発行された Cookie は CookieJar に入っているので hidden を付けて一度だけ再送する
+        for (name, value) in read_confirmation_inputs(&text) {
+            if let Some(field) = form.iter_mut().find(|(k, _)| *k == name) {
+                // 利用者の入力は確認ページの値ではなく元の値を使う
+                if !["subject", "FROM", "mail", "MESSAGE"].contains(&name.as_str()) {
+                    field.1 = value;
+                }
+            } else {
+                form.push((name, value));
+            }
+        }
+        let text = self.send_form(charset, &form).await?;
+        Ok(parse_post_response(&text))
+    }
+}
+
+#[async_trait::async_trait]
+impl Board for CompatibleBoard {
+    fn board_url(&self) -> Url {
+        board_url(&self.origin, &self.bbs)
+    }
+
+    fn thread(&self, key: u64) -> Box<dyn Thread> {
+        Box::new(Compatible::new(self.clone(), key))
+    }
+
+    fn thread_url(&self, key: u64) -> Url {
+        let thread_url = format!("{}/test/read.cgi/{}/{}/", self.origin, self.bbs, key);
+        Url::parse(&thread_url).unwrap()
+    }
+
+    async fn board_settings(&self) -> Result<BoardSettings> {
+        fetch_board_settings(&self.client, &self.origin, &self.bbs).await
+    }
+
+    async fn fetch_thread_list(&self) -> Result<Vec<ThreadSummary>> {
+        fetch_thread_list(&self.client, &self.origin, &self.bbs).await
+    }
+
+    async fn create_thread(
+        &self,
+        charset: &str,
+        subject: &str,
+        name: &str,
+        email: &str,
+        msg: &str,
+    ) -> Result<Url> {
+        let encoding = encoding_for_label(charset)?;
+        match self.board_settings().await {
+            Ok(settings) => {
+                settings.validate_subject(encoding, subject)?;
+                settings.validate(encoding, name, email, msg)?;
+            }
+            Err(err) => debug!("no SETTING.TXT: {:?}", err),
+        }
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let form = vec![
+            ("subject".to_owned(), subject.to_owned()),
+            ("FROM".to_owned(), name.to_owned()),
+            ("mail".to_owned(), email.to_owned()),
+            ("MESSAGE".to_owned(), msg.to_owned()),
+            ("bbs".to_owned(), self.bbs.clone()),
+            ("time".to_owned(), time.to_string()),
+            ("submit".to_owned(), "新規スレッド作成".to_owned()),
+        ];
+        let outcome = self.submit(charset, form, name, email).await?;
+        outcome.into_result()?;
+        let threads = self.fetch_thread_list().await?;
+        let key = find_thread_by_title(&threads, subject).ok_or_else(|| BbsError::ParseError {
+            context: "subject.txt".to_owned(),
+            message: format!("立てたスレッドが見つかりません: {}", subject),
+        })?;
+        Ok(self.thread_url(key))
+    }
+}
+
+pub struct CompatibleProvider;
+
+impl BbsProvider for CompatibleProvider {
+    fn name(&self) -> &'static str {
+        "2ch 互換"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            create_thread: true,
+            board_settings: true,
+        }
+    }
+
+    fn board_url_and_key(&self, url: &Url) -> Option<(Url, Option<u64>)> {
+        let (bbs, key) = match parse_thread_path(url) {
+            Some((bbs, key)) => (bbs, Some(key)),
+            None => (parse_board_url(url)?, None),
+        };
+        Some((board_url(&url.origin().ascii_serialization(), &bbs), key))
+    }
+
+    fn parse_thread_url(&self, client: &BbsClient, url: &Url) -> Option<Box<dyn Thread>> {
+        Some(Box::new(parse_thread_url(client, url)?))
+    }
+
+    fn parse_board_url(&self, client: &BbsClient, url: &Url) -> Option<Box<dyn Board>> {
+        let bbs = parse_board_url(url)?;
+        let origin = url.origin().ascii_serialization();
+        Some(Box::new(CompatibleBoard::new(client.clone(), origin, bbs)))
+    }
+}
+
+pub struct Compatible {
+    board: CompatibleBoard,
+    key: u64,
+    dat: Mutex<DatCache>,
+}
+
+impl Compatible {
+    fn new(board: CompatibleBoard, key: u64) -> Self {
+        Self {
+            board,
+            key,
+            dat: Mutex::new(DatCache::default()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Thread for Compatible {
+    fn key(&self) -> u64 {
+        self.key
+    }
+
+    fn board(&self) -> Box<dyn Board> {
+        Box::new(self.board.clone())
+    }
+
+    async fn post(&self, charset: &str, name: &str, email: &str, msg: &str) -> Result<PostOutcome> {
+        let encoding = encoding_for_label(charset)?;
+        match self.board.board_settings().await {
+            Ok(settings) => settings.validate(encoding, name, email, msg)?,
+            Err(err) => debug!("no SETTING.TXT: {:?}", err),
+        }
+        let form = vec![
+            ("FROM".to_owned(), name.to_owned()),
+            ("mail".to_owned(), email.to_owned()),
+            ("MESSAGE".to_owned(), msg.to_owned()),
+            ("key".to_owned(), self.key.to_string()),
+            ("bbs".to_owned(), self.board.bbs.clone()),
+        ];
+        self.board.submit(charset, form, name, email).await
+    }
+}
+
+#[async_trait::async_trait]
+impl ThreadReader for Compatible {
+    async fn read(&self, charset: &str, from: u32) -> Result<Vec<Res>> {
+        let encoding = encoding_for_label(charset)?;
+        let dat_url = format!(
+            "{}/{}/dat/{}.dat",
+            self.board.origin, self.board.bbs, self.key
+        );
+        let mut dat = self.dat.lock().await;
+        dat.fetch(&self.board.client, &dat_url).await?;
+        let (text, _, _) = dat.charset().unwrap_or(encoding).decode(dat.bytes());
+        Ok(parse_dat(&text)
+            .into_iter()
+            .filter(|res| res.number >= from)
+            .collect())
     }
 }
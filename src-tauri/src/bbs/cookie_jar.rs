@@ -0,0 +1,201 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use cookie::Cookie;
+use reqwest::{cookie::CookieStore, header::HeaderValue};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use url::Url;
+
+use crate::app_dir::JsonSaver;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct StoredCookie {
+    name: String,
+    value: String,
+    /// 小文字のホスト名
+    domain: String,
+    /// Domain 属性が無ければ発行したホストだけに送る
+    host_only: bool,
+    path: String,
+    /// 期限 (UNIX 時間の秒)。無ければ消されるまで残す
+    expires: Option<i64>,
+}
+
+impl StoredCookie {
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires.is_some_and(|x| x <= now)
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_ascii_lowercase();
+        let domain_match = if self.host_only {
+            host == self.domain
+        } else {
+            host == self.domain || host.ends_with(&format!(".{}", self.domain))
+        };
+        domain_match && path_match(url.path(), &self.path)
+    }
+}
+
+/// RFC 6265 5.1.4 の path-match
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/'))
+}
+
+/// Path 属性が無いときの既定値。リクエストのパスの最後の `/` より前
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_owned(),
+        Some(i) => url.path()[..i].to_owned(),
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// `Set-Cookie` を読む。期限切れのものは削除の指示として `expires` が過去になる
+fn parse_set_cookie(set_cookie: &str, url: &Url, now: i64) -> Option<StoredCookie> {
+    let cookie = Cookie::parse(set_cookie).ok()?;
+    let host = url.host_str()?.to_ascii_lowercase();
+    let (domain, host_only) = match cookie.domain().map(str::to_ascii_lowercase) {
+        // 発行したホストと関係ないドメインには設定させない
+        Some(domain) if host == domain || host.ends_with(&format!(".{}", domain)) => {
+            (domain, false)
+        }
+        Some(_) => return None,
+        None => (host, true),
+    };
+    let path = cookie
+        .path()
+        .filter(|x| x.starts_with('/'))
+        .map(str::to_owned)
+        .unwrap_or_else(|| default_path(url));
+    // Max-Age は Expires より優先する
+    let expires = match cookie.max_age() {
+        Some(max_age) => Some(now + max_age.whole_seconds()),
+        None => cookie.expires_datetime().map(|x| x.unix_timestamp()),
+    };
+    Some(StoredCookie {
+        name: cookie.name().to_owned(),
+        value: cookie.value().to_owned(),
+        domain,
+        host_only,
+        path,
+        expires,
+    })
+}
+
+/// Cookie を保持し、変わったらファイルへ書き出す
+pub struct CookieJar {
+    cookies: Mutex<Vec<StoredCookie>>,
+    /// 書き出しは別のスレッドで行い、通信を待たせない
+    saver: Option<JsonSaver<Vec<StoredCookie>>>,
+}
+
+impl CookieJar {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let cookies = path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| {
+                serde_json::from_slice(&bytes)
+                    .inspect_err(|err| warn!("broken cookie jar: {:?}", err))
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self {
+            cookies: Mutex::new(cookies),
+            saver: path.map(|x| JsonSaver::spawn(x, Duration::ZERO)),
+        }
+    }
+
+    /// `origin` のホストの全てのパスに送る Cookie を設定する
+    pub fn set(&self, origin: &str, name: &str, value: &str) {
+        let Some(host) = Url::parse(origin)
+            .ok()
+            .and_then(|x| x.host_str().map(str::to_ascii_lowercase))
+        else {
+            return;
+        };
+        self.update(vec![StoredCookie {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            domain: host,
+            host_only: true,
+            path: "/".to_owned(),
+            expires: None,
+        }]);
+    }
+
+    /// 同じ名前・ドメイン・パスのものを置き換え、期限切れのものは消す
+    fn update(&self, new_cookies: Vec<StoredCookie>) {
+        let now = now();
+        let snapshot = {
+            let mut cookies = self.cookies.lock().unwrap();
+            let before = cookies.clone();
+            for cookie in new_cookies {
+                cookies.retain(|x| {
+                    (&x.name, &x.domain, &x.path) != (&cookie.name, &cookie.domain, &cookie.path)
+                });
+                if !cookie.is_expired(now) {
+                    cookies.push(cookie);
+                }
+            }
+            cookies.retain(|x| !x.is_expired(now));
+            if *cookies == before {
+                return;
+            }
+            cookies.clone()
+        };
+        if let Some(saver) = &self.saver {
+            saver.save(snapshot);
+        }
+    }
+}
+
+impl CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let now = now();
+        let cookies = cookie_headers
+            .filter_map(|x| x.to_str().ok())
+            .filter_map(|x| parse_set_cookie(x, url, now))
+            .collect::<Vec<_>>();
+        if !cookies.is_empty() {
+            self.update(cookies);
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let now = now();
+        let cookies = self.cookies.lock().unwrap();
+        let mut matched = cookies
+            .iter()
+            .filter(|x| !x.is_expired(now) && x.matches(url))
+            .collect::<Vec<_>>();
+        // パスが長いものを先に送る
+        matched.sort_by_key(|x| std::cmp::Reverse(x.path.len()));
+        let header = matched
+            .iter()
+            .map(|x| format!("{}={}", x.name, x.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if header.is_empty() {
+            return None;
+        }
+        HeaderValue::from_str(&header).ok()
+    }
+}
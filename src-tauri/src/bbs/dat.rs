@@ -0,0 +1,93 @@
+use encoding_rs::Encoding;
+use reqwest::{
+    StatusCode,
+    header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RANGE},
+};
+use tracing::debug;
+
+use super::{
+    client::{BbsClient, CheckStatus},
+    encoding::content_type_charset,
+    error::Result,
+};
+
+/// 前回取得した DAT と、差分取得に使う検証子
+#[derive(Default)]
+pub struct DatCache {
+    bytes: Vec<u8>,
+    last_modified: Option<String>,
+    etag: Option<String>,
+    charset: Option<&'static Encoding>,
+}
+
+impl DatCache {
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Content-Type で知らされた文字コード
+    pub fn charset(&self) -> Option<&'static Encoding> {
+        self.charset
+    }
+
+    fn update_validators(&mut self, resp: &reqwest::Response) {
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|x| x.to_str().ok())
+                .map(str::to_owned)
+        };
+        self.last_modified = header(LAST_MODIFIED).or(self.last_modified.take());
+        self.etag = header(ETAG).or(self.etag.take());
+        self.charset = content_type_charset(resp).or(self.charset);
+    }
+
+    async fn fetch_all(&mut self, client: &BbsClient, dat_url: &str) -> Result<()> {
+        let resp = client.get(dat_url).send().await?.check_status()?;
+        *self = Self::default();
+        self.update_validators(&resp);
+        self.bytes = resp.bytes().await?.to_vec();
+        Ok(())
+    }
+
+    /// 前回の続きだけを取得する。DAT が書き換えられていたら全体を取り直す
+    pub async fn fetch(&mut self, client: &BbsClient, dat_url: &str) -> Result<()> {
+        if self.bytes.is_empty() {
+            return self.fetch_all(client, dat_url).await;
+        }
+        // 末尾の改行も取り直し、あぼーん等で内容がずれていないか確かめる
+        let mut req = client
+            .get(dat_url)
+            .header(RANGE, format!("bytes={}-", self.bytes.len() - 1));
+        if let Some(last_modified) = &self.last_modified {
+            req = req.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        if let Some(etag) = &self.etag {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+        let resp = req.send().await?;
+        match resp.status() {
+            StatusCode::NOT_MODIFIED => Ok(()),
+            StatusCode::PARTIAL_CONTENT => {
+                self.update_validators(&resp);
+                let bytes = resp.bytes().await?;
+                if bytes.first() != self.bytes.last() {
+                    debug!("dat rewritten: {}", dat_url);
+                    return self.fetch_all(client, dat_url).await;
+                }
+                self.bytes.extend_from_slice(&bytes[1..]);
+                Ok(())
+            }
+            StatusCode::RANGE_NOT_SATISFIABLE => {
+                debug!("dat shrunk: {}", dat_url);
+                self.fetch_all(client, dat_url).await
+            }
+            _ => {
+                let resp = resp.check_status()?;
+                self.update_validators(&resp);
+                self.bytes = resp.bytes().await?.to_vec();
+                Ok(())
+            }
+        }
+    }
+}
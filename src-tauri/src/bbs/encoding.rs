@@ -0,0 +1,224 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{LazyLock, Mutex},
+};
+
+use encoding_rs::Encoding;
+use percent_encoding::{NON_ALPHANUMERIC, percent_encode};
+use regex::Regex;
+use reqwest::{Response, header::CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use url::Url;
+
+use super::error::{BbsError, Result};
+
+/// 板の文字コードで表せない文字の扱い
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnmappablePolicy {
+    /// `&#NNNN;` の数値文字参照にする
+    #[default]
+    NumericReference,
+    /// 置き換え表に従って置き換える。表に無いものは数値文字参照にする
+    Substitute(BTreeMap<String, String>),
+    /// 書き込まない
+    Refuse,
+}
+
+/// 表せなかった文字と、代わりに送る文字列。拒否した場合は `None`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unmappable {
+    pub text: String,
+    pub replacement: Option<String>,
+}
+
+static POLICIES: LazyLock<Mutex<HashMap<Url, UnmappablePolicy>>> = LazyLock::new(Default::default);
+
+pub fn set_unmappable_policy(board_url: Url, policy: UnmappablePolicy) {
+    POLICIES.lock().unwrap().insert(board_url, policy);
+}
+
+pub fn unmappable_policy(board_url: &Url) -> UnmappablePolicy {
+    POLICIES
+        .lock()
+        .unwrap()
+        .get(board_url)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// ZWJ や異体字セレクタ、肌の色などで後ろの文字とつながるか
+fn joins_previous(c: char) -> bool {
+    matches!(c, '\u{200D}' | '\u{FE00}'..='\u{FE0F}' | '\u{1F3FB}'..='\u{1F3FF}' | '\u{20E3}')
+        || matches!(c, '\u{E0020}'..='\u{E007F}')
+}
+
+/// 先頭の一まとまり
+fn first_cluster(text: &str) -> &str {
+    let mut after_zwj = false;
+    for (i, c) in text.char_indices() {
+        if i > 0 && !after_zwj && !joins_previous(c) {
+            return &text[..i];
+        }
+        after_zwj = c == '\u{200D}';
+    }
+    text
+}
+
+/// 絵文字の ZWJ シーケンスなどを一まとまりとして分ける
+pub fn clusters(text: &str) -> Vec<&str> {
+    let mut clusters = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let cluster = first_cluster(rest);
+        clusters.push(cluster);
+        rest = &rest[cluster.len()..];
+    }
+    clusters
+}
+
+fn is_mappable(encoding: &'static Encoding, text: &str) -> bool {
+    !encoding.encode(text).2
+}
+
+fn numeric_reference(encoding: &'static Encoding, text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            let c_str = c.to_string();
+            if is_mappable(encoding, &c_str) {
+                c_str
+            } else {
+                format!("&#{};", c as u32)
+            }
+        })
+        .collect()
+}
+
+/// 表せない文字を方針に従って置き換える。置き換えたものも返す
+fn apply_policy(
+    encoding: &'static Encoding,
+    text: &str,
+    policy: &UnmappablePolicy,
+) -> (String, Vec<Unmappable>) {
+    let mut result = String::new();
+    let mut affected = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        // 置き換え表は長いものから当てる
+        if let UnmappablePolicy::Substitute(table) = policy
+            && let Some((from, to)) = table
+                .iter()
+                .filter(|(from, _)| !from.is_empty() && rest.starts_with(from.as_str()))
+                .filter(|(from, _)| !is_mappable(encoding, from))
+                .max_by_key(|(from, _)| from.len())
+        {
+            result.push_str(to);
+            affected.push(Unmappable {
+                text: from.clone(),
+                replacement: Some(to.clone()),
+            });
+            rest = &rest[from.len()..];
+            continue;
+        }
+        let cluster = first_cluster(rest);
+        rest = &rest[cluster.len()..];
+        if is_mappable(encoding, cluster) {
+            result.push_str(cluster);
+            continue;
+        }
+        let replacement = match policy {
+            UnmappablePolicy::Refuse => None,
+            _ => Some(numeric_reference(encoding, cluster)),
+        };
+        result.push_str(replacement.as_deref().unwrap_or_default());
+        affected.push(Unmappable {
+            text: cluster.to_owned(),
+            replacement,
+        });
+    }
+    (result, affected)
+}
+
+/// 書き込む前に、表せない文字とその扱いを調べる
+pub fn find_unmappable(
+    encoding: &'static Encoding,
+    text: &str,
+    policy: &UnmappablePolicy,
+) -> Vec<Unmappable> {
+    apply_policy(encoding, text, policy).1
+}
+
+pub fn charset_percent_encode(
+    encoding: &'static Encoding,
+    text: &str,
+    policy: &UnmappablePolicy,
+) -> Result<String> {
+    let (text, affected) = apply_policy(encoding, text, policy);
+    if *policy == UnmappablePolicy::Refuse && !affected.is_empty() {
+        return Err(BbsError::UnmappableCharacters(
+            affected.into_iter().map(|x| x.text).collect(),
+        ));
+    }
+    if !affected.is_empty() {
+        info!("unmappable characters: {:?}", affected);
+    }
+    let (bytes, _, _) = encoding.encode(&text);
+    Ok(percent_encode(&bytes, NON_ALPHANUMERIC).to_string())
+}
+
+/// `application/x-www-form-urlencoded` の本文を作る
+pub fn encode_form(
+    encoding: &'static Encoding,
+    policy: &UnmappablePolicy,
+    form: &[(impl AsRef<str>, impl AsRef<str>)],
+) -> Result<String> {
+    Ok(form
+        .iter()
+        .map(|(k, v)| {
+            let v = charset_percent_encode(encoding, v.as_ref(), policy)?;
+            Ok(format!("{}={}", k.as_ref(), v))
+        })
+        .collect::<Result<Vec<_>>>()?
+        .join("&"))
+}
+
+/// スレッドのページから分かった文字コード。オリジンごとに覚えておく
+static DISCOVERED_CHARSETS: LazyLock<Mutex<HashMap<String, &'static Encoding>>> =
+    LazyLock::new(Default::default);
+
+pub fn remember_charset(url: &Url, encoding: &'static Encoding) {
+    DISCOVERED_CHARSETS
+        .lock()
+        .unwrap()
+        .insert(url.origin().ascii_serialization(), encoding);
+}
+
+fn discovered_charset(url: &Url) -> Option<&'static Encoding> {
+    DISCOVERED_CHARSETS
+        .lock()
+        .unwrap()
+        .get(&url.origin().ascii_serialization())
+        .copied()
+}
+
+/// `Content-Type: text/plain; charset=...` の charset を読む
+pub fn content_type_charset(resp: &Response) -> Option<&'static Encoding> {
+    let content_type = resp.headers().get(CONTENT_TYPE)?.to_str().ok()?;
+    let c = Regex::new(r#"(?i)charset\s*=\s*"?([^;"\s]+)"#)
+        .unwrap()
+        .captures(content_type)?;
+    Encoding::for_label(c[1].as_bytes())
+}
+
+/// 応答の文字コード。Content-Type、スレッドのページから分かったもの、`default` の順に使う
+pub fn response_charset(resp: &Response, default: &'static Encoding) -> &'static Encoding {
+    content_type_charset(resp)
+        .or_else(|| discovered_charset(resp.url()))
+        .unwrap_or(default)
+}
+
+pub async fn decode_response(resp: Response, default: &'static Encoding) -> Result<String> {
+    let encoding = response_charset(&resp, default);
+    let bytes = resp.bytes().await?;
+    Ok(encoding.decode(&bytes).0.into_owned())
+}
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use encoding_rs::Encoding;
+use reqwest::StatusCode;
+
+use super::ValidationError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BbsError {
+    #[error("掲示板の URL ではありません: {0}")]
+    InvalidUrl(String),
+    #[error("プロキシの指定が正しくありません: {0}")]
+    InvalidProxy(String),
+    #[error("対応していない文字コードです: {0}")]
+    UnsupportedCharset(String),
+    #[error("この掲示板では使えません: {0}")]
+    Unsupported(&'static str),
+    #[error("通信に失敗しました: {0}")]
+    Network(#[source] reqwest::Error),
+    #[error("サーバーがエラーを返しました ({status}): {url}")]
+    HttpStatus {
+        status: StatusCode,
+        url: String,
+        /// 応答に本文が無かった
+        empty_body: bool,
+    },
+    #[error("板にスレッドがありません")]
+    EmptyBoard,
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+    #[error("板の文字コードで書けない文字があります: {}", .0.join(" "))]
+    UnmappableCharacters(Vec<String>),
+    #[error("書き込めませんでした: {0}")]
+    Rejected(String),
+    #[error("連投規制中です")]
+    RateLimited { retry_after: Option<Duration> },
+    #[error("スレッドが止まっています")]
+    ThreadStopped,
+    #[error("{context} を読めませんでした: {message}")]
+    ParseError { context: String, message: String },
+    /// 分けて書き込んだ途中で失敗した。`posted` 件目までは書き込めている
+    #[error("{total} 件中 {posted} 件まで書き込みました: {source}")]
+    PartiallyPosted {
+        posted: usize,
+        total: usize,
+        source: Box<BbsError>,
+    },
+}
+
+pub type Result<T, E = BbsError> = std::result::Result<T, E>;
+
+impl From<reqwest::Error> for BbsError {
+    fn from(err: reqwest::Error) -> Self {
+        BbsError::Network(err)
+    }
+}
+
+/// 利用者やサーバーが指定した文字コード名から `Encoding` を得る
+pub fn encoding_for_label(charset: &str) -> Result<&'static Encoding> {
+    Encoding::for_label(charset.as_bytes())
+        .ok_or_else(|| BbsError::UnsupportedCharset(charset.to_owned()))
+}
@@ -0,0 +1,45 @@
+use regex::{Captures, Regex};
+
+/// 文字参照を展開する。未知の名前付き参照はそのまま残す
+pub fn decode_entities(text: &str) -> String {
+    Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);")
+        .unwrap()
+        .replace_all(text, |c: &Captures| {
+            let entity = &c[1];
+            let ch = if let Some(hex) = entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+            {
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+            } else if let Some(dec) = entity.strip_prefix('#') {
+                dec.parse().ok().and_then(char::from_u32)
+            } else {
+                match entity {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some(' '),
+                    "hearts" => Some('♥'),
+                    _ => None,
+                }
+            };
+            ch.map(String::from).unwrap_or_else(|| c[0].to_owned())
+        })
+        .into_owned()
+}
+
+/// タグを取り除いてテキストにする。`<br>` は改行になる
+pub fn strip_tags(html: &str) -> String {
+    let text = Regex::new(r"(?is)<(script|style|title)[^>]*>.*?</\s*(script|style|title)\s*>")
+        .unwrap()
+        .replace_all(html, "");
+    let text = Regex::new(r"(?i) ?<br\s*/?> ?")
+        .unwrap()
+        .replace_all(&text, "\n");
+    let text = Regex::new(r"(?s)<!--.*?-->|<[^>]*>")
+        .unwrap()
+        .replace_all(&text, "");
+    decode_entities(&text)
+}
@@ -0,0 +1,264 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use encoding_rs::SHIFT_JIS;
+use regex::Regex;
+use reqwest::header::{CONTENT_TYPE, REFERER};
+use tracing::debug;
+use url::Url;
+
+use super::{
+    BbsError, BbsProvider, Board, Capabilities, PostOutcome, Res, Thread, ThreadReader,
+    board_settings::BoardSettings,
+    client::{BbsClient, CheckStatus},
+    encoding::{decode_response, encode_form, unmappable_policy},
+    error::{Result, encoding_for_label},
+    html::decode_entities,
+    outcome::parse_post_response,
+    res::{parse_body, parse_name, split_date_id},
+    subject::{ThreadSummary, read_cgi_thread_list},
+};
+
+fn is_machi_host(url: &Url) -> bool {
+    url.host_str()
+        .is_some_and(|x| x == "machi.to" || x.ends_with(".machi.to"))
+}
+
+/// `/bbs/read.cgi/BOARD/KEY/` を `(BOARD, KEY)` にする
+fn parse_thread_path(thread_url: &Url) -> Option<(String, u64)> {
+    if !is_machi_host(thread_url) {
+        return None;
+    }
+    let c = Regex::new(r"^/bbs/read.cgi/([0-9A-Za-z_]+)/([0-9]+)(:?/.*)?$")
+        .unwrap()
+        .captures(thread_url.path())?;
+    Some((
+        c.get(1).unwrap().as_str().to_string(),
+        c.get(2).unwrap().as_str().parse().ok()?,
+    ))
+}
+
+pub fn parse_thread_url(client: &BbsClient, thread_url: &Url) -> Option<Machi> {
+    let origin = thread_url.origin().ascii_serialization();
+    let (board, key) = parse_thread_path(thread_url)?;
+    Some(Machi {
+        board: MachiBoard::new(client.clone(), origin, board),
+        key,
+    })
+}
+
+pub fn parse_board_url(board_url: &Url) -> Option<String> {
+    if !is_machi_host(board_url) {
+        return None;
+    }
+    let c = Regex::new(r"^/([0-9A-Za-z_]+)/?$")
+        .unwrap()
+        .captures(board_url.path())?;
+    Some(c.get(1).unwrap().as_str().to_string())
+}
+
+fn board_url(origin: &str, board: &str) -> Url {
+    Url::parse(&format!("{}/{}/", origin, board)).unwrap()
+}
+
+async fn fetch_subject_txt(client: &BbsClient, origin: &str, board: &str) -> Result<String> {
+    let subject_url = format!("{}/{}/subject.txt", origin, board);
+    let resp = client.get(subject_url).send().await?.check_status()?;
+    decode_response(resp, SHIFT_JIS).await
+}
+
+pub async fn fetch_thread_list(
+    client: &BbsClient,
+    origin: &str,
+    board: &str,
+) -> Result<Vec<ThreadSummary>> {
+    let subject_txt = fetch_subject_txt(client, origin, board).await?;
+    Ok(read_cgi_thread_list(&subject_txt))
+}
+
+/// offlaw.cgi の `NUM<>NAME<>MAIL<>DATE ID<>BODY<>TITLE` 形式を読む
+pub(super) fn parse_offlaw(text: &str) -> Vec<Res> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split("<>");
+            let number = fields.next()?.parse().ok()?;
+            let name = fields.next()?;
+            let mail = fields.next()?;
+            let date_id = fields.next()?;
+            let body = fields.next()?;
+            let (date, id) = split_date_id(date_id);
+            Some(Res {
+                number,
+                name: parse_name(name),
+                mail: decode_entities(mail),
+                date,
+                id,
+                body: parse_body(body),
+            })
+        })
+        .collect()
+}
+
+pub struct MachiProvider;
+
+impl BbsProvider for MachiProvider {
+    fn name(&self) -> &'static str {
+        "まちBBS"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            create_thread: false,
+            board_settings: false,
+        }
+    }
+
+    fn board_url_and_key(&self, url: &Url) -> Option<(Url, Option<u64>)> {
+        let (board, key) = match parse_thread_path(url) {
+            Some((board, key)) => (board, Some(key)),
+            None => (parse_board_url(url)?, None),
+        };
+        Some((board_url(&url.origin().ascii_serialization(), &board), key))
+    }
+
+    fn parse_thread_url(&self, client: &BbsClient, url: &Url) -> Option<Box<dyn Thread>> {
+        Some(Box::new(parse_thread_url(client, url)?))
+    }
+
+    fn parse_board_url(&self, client: &BbsClient, url: &Url) -> Option<Box<dyn Board>> {
+        let board = parse_board_url(url)?;
+        let origin = url.origin().ascii_serialization();
+        Some(Box::new(MachiBoard::new(client.clone(), origin, board)))
+    }
+}
+
+#[derive(Clone)]
+pub struct MachiBoard {
+    client: BbsClient,
+    origin: String,
+    board: String,
+}
+
+impl MachiBoard {
+    pub fn new(client: BbsClient, origin: String, board: String) -> Self {
+        Self {
+            client,
+            origin,
+            board,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Board for MachiBoard {
+    fn board_url(&self) -> Url {
+        board_url(&self.origin, &self.board)
+    }
+
+    fn thread(&self, key: u64) -> Box<dyn Thread> {
+        Box::new(Machi {
+            board: self.clone(),
+            key,
+        })
+    }
+
+    fn thread_url(&self, key: u64) -> Url {
+        let thread_url = format!("{}/bbs/read.cgi/{}/{}/", self.origin, self.board, key);
+        Url::parse(&thread_url).unwrap()
+    }
+
+    async fn board_settings(&self) -> Result<BoardSettings> {
+        Err(BbsError::Unsupported("板の設定"))
+    }
+
+    async fn fetch_thread_list(&self) -> Result<Vec<ThreadSummary>> {
+        fetch_thread_list(&self.client, &self.origin, &self.board).await
+    }
+
+    async fn create_thread(
+        &self,
+        _charset: &str,
+        _subject: &str,
+        _name: &str,
+        _email: &str,
+        _msg: &str,
+    ) -> Result<Url> {
+        Err(BbsError::Unsupported("スレッド作成"))
+    }
+}
+
+pub struct Machi {
+    board: MachiBoard,
+    key: u64,
+}
+
+#[async_trait::async_trait]
+impl Thread for Machi {
+    fn key(&self) -> u64 {
+        self.key
+    }
+
+    fn board(&self) -> Box<dyn Board> {
+        Box::new(self.board.clone())
+    }
+
+    async fn post(&self, charset: &str, name: &str, email: &str, msg: &str) -> Result<PostOutcome> {
+        let encoding = encoding_for_label(charset)?;
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let policy = unmappable_policy(&self.board.board_url());
+        let body = encode_form(
+            encoding,
+            &policy,
+            &[
+                ("BBS", self.board.board.as_str()),
+                ("KEY", &self.key.to_string()),
+                ("TIME", &time.to_string()),
+                ("NAME", name),
+                ("MAIL", email),
+                ("MESSAGE", msg),
+                ("submit", "書き込む"),
+            ],
+        )?;
+        let resp = self
+            .board
+            .client
+            .post(format!("{}/bbs/write.cgi", self.board.origin))
+            .header(
+                CONTENT_TYPE,
+                format!("application/x-www-form-urlencoded; charset={}", charset),
+            )
+            .header(REFERER, self.board.thread_url(self.key).as_str())
+            .body(body)
+            .send()
+            .await?
+            .check_status()?;
+        let text = decode_response(resp, encoding).await?;
+        debug!("post resp: {}", text);
+        Ok(parse_post_response(&text))
+    }
+}
+
+#[async_trait::async_trait]
+impl ThreadReader for Machi {
+    async fn read(&self, charset: &str, from: u32) -> Result<Vec<Res>> {
+        let encoding = encoding_for_label(charset)?;
+        let offlaw_url = format!(
+            "{}/bbs/offlaw.cgi/2/{}/{}/{}-",
+            self.board.origin,
+            self.board.board,
+            self.key,
+            from.max(1)
+        );
+        let resp = self
+            .board
+            .client
+            .get(offlaw_url)
+            .send()
+            .await?
+            .check_status()?;
+        let text = decode_response(resp, encoding).await?;
+        Ok(parse_offlaw(&text))
+    }
+}
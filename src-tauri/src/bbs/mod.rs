@@ -1,76 +1,114 @@
+mod board_settings;
+mod client;
 mod compatible;
+mod cookie_jar;
+mod dat;
+mod encoding;
+mod error;
+mod html;
+mod machi;
+mod next_thread;
+mod outcome;
+mod provider;
+mod res;
+mod retry;
 mod shitaraba;
+mod split;
+mod subject;
 #[cfg(test)]
 mod test;
+mod trip;
 
 use core::str;
+use std::sync::{Arc, LazyLock};
 
-use anyhow::{Result, anyhow};
 use encoding_rs::{Encoding, UTF_8};
 use futures::StreamExt;
 use regex::Regex;
 use url::Url;
 
-use self::compatible::Compatible;
-use self::shitaraba::Shitaraba;
+use crate::app_dir::config_dir;
+
+pub use self::board_settings::{BoardSettings, ValidationError};
+use self::client::CheckStatus;
+pub use self::client::{BbsClient, BbsClientConfig};
+use self::cookie_jar::CookieJar;
+pub use self::encoding::{Unmappable, UnmappablePolicy, find_unmappable, set_unmappable_policy};
+use self::encoding::{content_type_charset, remember_charset};
+pub use self::error::{BbsError, Result, encoding_for_label};
+pub use self::next_thread::{NextThread, post_following_next_thread};
+pub use self::outcome::PostOutcome;
+pub use self::provider::{
+    BbsProvider, Capabilities, board_url_and_key, capabilities, new, parse_bbs_url,
+};
+pub use self::res::Res;
+pub use self::retry::{RetryPolicy, RetryProgress, is_safe_to_resend};
+pub use self::split::{post_parts, split_message};
+pub use self::subject::ThreadSummary;
+pub use self::trip::name_with_trip;
 
 pub const UA: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
 
+static COOKIE_JAR: LazyLock<Arc<CookieJar>> = LazyLock::new(|| {
+    // テストで利用者の Cookie を書き換えないようにする
+    let path = if cfg!(test) {
+        None
+    } else {
+        config_dir().map(|x| x.join("cookies.json"))
+    };
+    Arc::new(CookieJar::load(path))
+});
+
 #[async_trait::async_trait]
-pub trait Thread: Send + Sync {
-    async fn post(&self, charset: &str, name: &str, email: &str, msg: &str) -> Result<()>;
+pub trait Thread: ThreadReader + Send + Sync {
+    fn key(&self) -> u64;
+    fn board(&self) -> Box<dyn Board>;
+    async fn post(&self, charset: &str, name: &str, email: &str, msg: &str) -> Result<PostOutcome>;
 }
 
-pub async fn new(url: &Url) -> Result<Box<dyn Thread>> {
-    let host = url.host_str().ok_or_else(|| anyhow!("No host"))?;
-    let path = url.path();
-    if is_shitaraba_bbs(host, path) {
-        Ok(Box::new(Shitaraba::new(url).await?))
-    } else {
-        Ok(Box::new(Compatible::new(url).await?))
-    }
+#[async_trait::async_trait]
+pub trait Board: Send + Sync {
+    fn board_url(&self) -> Url;
+    /// この板の `key` のスレッド
+    fn thread(&self, key: u64) -> Box<dyn Thread>;
+    /// この板の `key` のスレッドの URL
+    fn thread_url(&self, key: u64) -> Url;
+    async fn board_settings(&self) -> Result<BoardSettings>;
+    async fn fetch_thread_list(&self) -> Result<Vec<ThreadSummary>>;
+    /// スレッドを立て、立てたスレッドの URL を返す
+    async fn create_thread(
+        &self,
+        charset: &str,
+        subject: &str,
+        name: &str,
+        email: &str,
+        msg: &str,
+    ) -> Result<Url>;
 }
 
-fn is_shitaraba_bbs(host: &str, path: &str) -> bool {
-    if host == "jbbs.shitaraba.net" {
-        return true;
-    }
-    if path.starts_with("/bbs/read.cgi/") {
-        return true;
-    }
-    false
+#[async_trait::async_trait]
+pub trait ThreadReader: Send + Sync {
+    /// `from` 番以降のレスを取得する
+    async fn read(&self, charset: &str, from: u32) -> Result<Vec<Res>>;
 }
 
 pub enum BbsUrl {
-    ProbablyShitarabaThread(Url, Box<dyn Thread>),
-    ProbablyShitarabaBoard(Url, String, u64),
-    ProbablyCompatibleThread(Url, Box<dyn Thread>),
-    MaybeCompatibleBoard(Url, String),
+    Thread(Url, Box<dyn Thread>),
+    Board(Url, Box<dyn Board>),
 }
 
-pub fn parse_bbs_url(url: Url) -> Result<BbsUrl, Url> {
-    if let Some(thread) = shitaraba::parse_thread_url(&url) {
-        return Ok(BbsUrl::ProbablyShitarabaThread(url, Box::new(thread)));
-    }
-    if let Some((dir, board)) = shitaraba::parse_board_url(&url) {
-        return Ok(BbsUrl::ProbablyShitarabaBoard(url, dir, board));
-    }
-    if let Some(thread) = compatible::parse_thread_url(&url) {
-        return Ok(BbsUrl::ProbablyCompatibleThread(url, Box::new(thread)));
-    }
-    if let Some(board) = compatible::parse_board_url(&url) {
-        return Ok(BbsUrl::MaybeCompatibleBoard(url, board));
+impl BbsUrl {
+    pub fn into_board(self) -> Box<dyn Board> {
+        match self {
+            BbsUrl::Thread(_, thread) => thread.board(),
+            BbsUrl::Board(_, board) => board,
+        }
     }
-    Err(url)
 }
 
-async fn fetch_charset_title_pair(url: &Url) -> Result<(String, String)> {
-    let resp = reqwest::Client::new()
-        .get(url.clone())
-        .header("User-Agent", UA)
-        .send()
-        .await?
-        .error_for_status()?;
+async fn fetch_charset_title_pair(client: &BbsClient, url: &Url) -> Result<(String, String)> {
+    let resp = client.get(url.clone()).send().await?.check_status()?;
+    let header_charset = content_type_charset(&resp);
     let mut bytes_stream = resp.bytes_stream();
     let mut buf = Vec::new();
     while let Some(chunk) = bytes_stream.next().await {
@@ -81,6 +119,11 @@ async fn fetch_charset_title_pair(url: &Url) -> Result<(String, String)> {
         }
     }
     let (charset, text) = 'block: {
+        if let Some(encoding) = header_charset {
+            remember_charset(url, encoding);
+            let (text, _, _) = encoding.decode(&buf);
+            break 'block (encoding.name().to_owned(), text);
+        }
         let Some(charset) = regex::bytes::Regex::new(r#"charset=([^;"]+)|charset="(.+)""#)
             .unwrap()
             .captures(&buf)
@@ -95,6 +138,7 @@ async fn fetch_charset_title_pair(url: &Url) -> Result<(String, String)> {
         let Some(encoding) = Encoding::for_label(charset) else {
             break 'block (UTF_8.name().to_owned(), String::from_utf8_lossy(&buf));
         };
+        remember_charset(url, encoding);
         let (text, _, _) = encoding.decode(&buf);
         (String::from_utf8_lossy(charset).to_string(), text)
     };
@@ -108,26 +152,20 @@ async fn fetch_charset_title_pair(url: &Url) -> Result<(String, String)> {
     ))
 }
 
-pub async fn fetch_thread_url_encoding_name(bbs_url: &BbsUrl) -> Result<(Url, String, String)> {
+pub async fn fetch_thread_url_encoding_name(
+    client: &BbsClient,
+    bbs_url: &BbsUrl,
+) -> Result<(Url, String, String)> {
     match bbs_url {
-        BbsUrl::ProbablyShitarabaThread(url, _thread) => {
-            let (encoding, title) = fetch_charset_title_pair(url).await?;
-            Ok((url.clone(), encoding, title))
-        }
-        BbsUrl::ProbablyShitarabaBoard(url, dir, board) => {
-            let origin = url.origin().ascii_serialization();
-            let url = shitaraba::fetch_latest_thread_url(&origin, dir, *board).await?;
-            let (encoding, title) = fetch_charset_title_pair(&url).await?;
-            Ok((url, encoding, title))
-        }
-        BbsUrl::ProbablyCompatibleThread(url, _thread) => {
-            let (encoding, title) = fetch_charset_title_pair(url).await?;
+        BbsUrl::Thread(url, _thread) => {
+            let (encoding, title) = fetch_charset_title_pair(client, url).await?;
             Ok((url.clone(), encoding, title))
         }
-        BbsUrl::MaybeCompatibleBoard(url, board) => {
-            let origin = url.origin().ascii_serialization();
-            let url = compatible::fetch_latest_thread_url(&origin, board).await?;
-            let (encoding, title) = fetch_charset_title_pair(&url).await?;
+        BbsUrl::Board(_url, board) => {
+            let threads = board.fetch_thread_list().await?;
+            let latest = threads.first().ok_or(BbsError::EmptyBoard)?;
+            let url = board.thread_url(latest.key);
+            let (encoding, title) = fetch_charset_title_pair(client, &url).await?;
             Ok((url, encoding, title))
         }
     }
@@ -0,0 +1,159 @@
+use std::collections::HashSet;
+
+use regex::Regex;
+use tracing::{debug, info};
+use url::Url;
+
+use super::{
+    PostOutcome, Res, Thread, ThreadSummary, capabilities,
+    error::Result,
+    retry::{RetryPolicy, RetryProgress, post_with_retry},
+};
+
+/// 最後の何レスから次スレへのリンクを探すか
+const LINK_SEARCH_RESPONSES: u32 = 20;
+/// 次スレとみなすタイトルの類似度の下限
+const MIN_TITLE_SIMILARITY: f64 = 0.5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NextThread {
+    pub key: u64,
+    pub url: Url,
+    pub title: String,
+}
+
+/// 番号や記号を除いてタイトルを比べられる形にする
+fn normalize_title(title: &str) -> Vec<char> {
+    title
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// 文字 bigram の Dice 係数
+fn title_similarity(a: &str, b: &str) -> f64 {
+    let bigrams = |title: &str| -> HashSet<(char, char)> {
+        let chars = normalize_title(title);
+        chars.windows(2).map(|x| (x[0], x[1])).collect()
+    };
+    let a = bigrams(a);
+    let b = bigrams(b);
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    2.0 * a.intersection(&b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+/// レス本文に貼られたスレッド URL のキーを新しいレスから順に返す
+fn linked_keys(responses: &[Res]) -> Vec<u64> {
+    let url = Regex::new(r#"https?://[^\s<>"]+"#).unwrap();
+    let key = Regex::new(r"/([0-9]{9,10})(?:/|$)").unwrap();
+    responses
+        .iter()
+        .rev()
+        .flat_map(|res| url.find_iter(&res.body))
+        .filter_map(|x| key.captures(x.as_str()))
+        .filter_map(|c| c[1].parse().ok())
+        .collect()
+}
+
+/// スレ一覧と最後のレスから次スレらしきものを選ぶ
+pub fn choose_next_thread<'a>(
+    current: &ThreadSummary,
+    threads: &'a [ThreadSummary],
+    last_responses: &[Res],
+    thread_stop: u32,
+) -> Option<&'a ThreadSummary> {
+    let candidates: Vec<_> = threads
+        .iter()
+        .filter(|x| x.key > current.key && x.res_count < thread_stop)
+        .collect();
+    // 次スレのリンクが貼られていればそれを信じる
+    if let Some(thread) = linked_keys(last_responses)
+        .into_iter()
+        .find_map(|key| candidates.iter().find(|x| x.key == key))
+    {
+        return Some(thread);
+    }
+    candidates
+        .into_iter()
+        .map(|x| (title_similarity(&current.title, &x.title), x))
+        .filter(|(similarity, _)| *similarity >= MIN_TITLE_SIMILARITY)
+        // 似ている順、同じならレスが少なく古い方 (直後に立ったもの) を選ぶ
+        .max_by(|(a_similarity, a), (b_similarity, b)| {
+            a_similarity
+                .total_cmp(b_similarity)
+                .then(b.res_count.cmp(&a.res_count))
+                .then(b.key.cmp(&a.key))
+        })
+        .map(|(_, x)| x)
+}
+
+/// 埋まった・止まったスレッドの次スレを探す
+pub async fn find_next_thread(thread: &dyn Thread, charset: &str) -> Result<Option<NextThread>> {
+    let board = thread.board();
+    let threads = board.fetch_thread_list().await?;
+    let current = threads
+        .iter()
+        .find(|x| x.key == thread.key())
+        .cloned()
+        .unwrap_or(ThreadSummary {
+            key: thread.key(),
+            title: String::new(),
+            res_count: 0,
+        });
+    let thread_stop = if capabilities(&board.board_url()).is_some_and(|x| x.board_settings) {
+        match board.board_settings().await {
+            Ok(settings) => settings.thread_stop.unwrap_or(1000),
+            Err(_) => 1000,
+        }
+    } else {
+        1000
+    };
+    let from = current.res_count.saturating_sub(LINK_SEARCH_RESPONSES);
+    let last_responses = thread
+        .read(charset, from)
+        .await
+        .inspect_err(|err| debug!("failed to read last responses: {:?}", err))
+        .unwrap_or_default();
+    Ok(
+        choose_next_thread(&current, &threads, &last_responses, thread_stop).map(|x| NextThread {
+            key: x.key,
+            url: board.thread_url(x.key),
+            title: x.title.clone(),
+        }),
+    )
+}
+
+/// 書き込み、スレッドが止まっていれば次スレを探して書き込み直す。移動した場合は移動先も返す
+pub async fn post_following_next_thread(
+    thread: &dyn Thread,
+    charset: &str,
+    name: &str,
+    email: &str,
+    msg: &str,
+    retry: &RetryPolicy,
+    on_retry: &(dyn Fn(RetryProgress) + Send + Sync),
+) -> Result<(PostOutcome, Option<NextThread>)> {
+    let outcome = post_with_retry(thread, charset, name, email, msg, retry, on_retry).await?;
+    if outcome != PostOutcome::ThreadStopped {
+        return Ok((outcome, None));
+    }
+    let Some(next) = find_next_thread(thread, charset).await? else {
+        return Ok((outcome, None));
+    };
+    info!("moving to next thread: {} {}", next.url, next.title);
+    let next_thread = thread.board().thread(next.key);
+    let outcome = post_with_retry(
+        next_thread.as_ref(),
+        charset,
+        name,
+        email,
+        msg,
+        retry,
+        on_retry,
+    )
+    .await?;
+    Ok((outcome, Some(next)))
+}
@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use regex::Regex;
+
+use super::{
+    error::{BbsError, Result},
+    html::strip_tags,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PostOutcome {
+    Accepted,
+    Rejected { reason: String },
+    ConfirmationRequired,
+    ThreadStopped,
+    RateLimited { retry_after: Option<Duration> },
+    Unknown { html: String },
+}
+
+impl PostOutcome {
+    /// 書き込めなかった結果をエラーにする
+    pub fn into_result(self) -> Result<()> {
+        match self {
+            PostOutcome::Accepted => Ok(()),
+            PostOutcome::Rejected { reason } => Err(BbsError::Rejected(reason)),
+            PostOutcome::ConfirmationRequired => Err(BbsError::Rejected(
+                "書き込み確認が終わりませんでした".to_owned(),
+            )),
+            PostOutcome::ThreadStopped => Err(BbsError::ThreadStopped),
+            PostOutcome::RateLimited { retry_after } => Err(BbsError::RateLimited { retry_after }),
+            PostOutcome::Unknown { html } => Err(BbsError::ParseError {
+                context: "書き込み結果".to_owned(),
+                message: html,
+            }),
+        }
+    }
+}
+
+fn read_title(html: &str) -> Option<String> {
+    Regex::new(r"(?is)<title>(.*?)</title>")
+        .unwrap()
+        .captures(html)
+        .map(|c| c[1].trim().to_owned())
+}
+
+/// 2ch 互換の bbs.cgi が埋め込む `<!-- 2ch_X:... -->` を読む
+fn read_2ch_x(html: &str) -> Option<String> {
+    Regex::new(r"<!--\s*2ch_X:(\w+)\s*-->")
+        .unwrap()
+        .captures(html)
+        .map(|c| c[1].to_owned())
+}
+
+fn read_error_reason(html: &str) -> String {
+    let text = strip_tags(html);
+    let prefix = Regex::new(r"^(?:ERROR|ＥＲＲＯＲ)[!！]?\s*[-:：]?\s*").unwrap();
+    let lines = text.lines().map(str::trim).filter(|x| !x.is_empty());
+    let first_line = lines.clone().next().unwrap_or_default();
+    lines
+        .filter(|x| prefix.is_match(x))
+        .map(|x| prefix.replace(x, "").into_owned())
+        .find(|x| !x.is_empty())
+        .unwrap_or_else(|| first_line.to_owned())
+}
+
+fn read_retry_after(text: &str) -> Option<Duration> {
+    let c = Regex::new(r"([0-9]+)\s*(sec|秒|分)")
+        .unwrap()
+        .captures(text)?;
+    let value: u64 = c[1].parse().ok()?;
+    Some(match &c[2] {
+        "分" => Duration::from_secs(value * 60),
+        _ => Duration::from_secs(value),
+    })
+}
+
+fn is_thread_stopped(text: &str) -> bool {
+    // 「を超えました」だけでは本文の長さや行数の上限と区別できないので、レス数の上限に限る
+    let over_limit = Regex::new(r"このスレッドは\s*[0-9０-９]+\s*を超えました").unwrap();
+    [
+        "スレッドストップ",
+        "このスレッドには書き込めません",
+        "このスレッドは過去ログ",
+        "THREAD STOP",
+        "スレッドが停止",
+    ]
+    .iter()
+    .any(|x| text.contains(x))
+        || over_limit.is_match(text)
+}
+
+fn is_rate_limited(text: &str) -> bool {
+    [
+        "連投規制",
+        "連続投稿",
+        "しばらくお待ちください",
+        "たたないと書けません",
+        "投稿間隔",
+        "時間を置いて",
+    ]
+    .iter()
+    .any(|x| text.contains(x))
+}
+
+/// bbs.cgi / write.cgi の応答 HTML から書き込み結果を判定する
+pub fn parse_post_response(html: &str) -> PostOutcome {
+    let title = read_title(html).unwrap_or_default();
+    match read_2ch_x(html).as_deref() {
+        // false は注意付きで書き込めたとき
+        Some("true") | Some("false") => return PostOutcome::Accepted,
+        Some("cookie") | Some("check") => return PostOutcome::ConfirmationRequired,
+        _ => {}
+    }
+    if title.contains("書き込み確認") || title.contains("書きこみ確認") {
+        return PostOutcome::ConfirmationRequired;
+    }
+    if title.contains("書きこみました") || title.contains("書き込みました") {
+        return PostOutcome::Accepted;
+    }
+    let text = strip_tags(html);
+    if is_thread_stopped(&text) {
+        return PostOutcome::ThreadStopped;
+    }
+    if is_rate_limited(&text) {
+        return PostOutcome::RateLimited {
+            retry_after: read_retry_after(&text),
+        };
+    }
+    if title.contains("ERROR") || title.contains("ＥＲＲＯＲ") || read_2ch_x(html).is_some() {
+        return PostOutcome::Rejected {
+            reason: read_error_reason(html),
+        };
+    }
+    PostOutcome::Unknown {
+        html: html.to_owned(),
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::{Arc, LazyLock};
+
+use tracing::debug;
+use url::Url;
+
+use super::{
+    BbsError, BbsUrl, Board, Thread, client::BbsClient, compatible::CompatibleProvider,
+    error::Result, machi::MachiProvider, shitaraba::ShitarabaProvider,
+};
+
+/// 掲示板ソフトごとに対応している機能
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// スレッドを立てられる
+    pub create_thread: bool,
+    /// SETTING.TXT などの板の設定を読める
+    pub board_settings: bool,
+}
+
+/// 掲示板ソフトの URL を判定し、スレッドや板を作る
+pub trait BbsProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn capabilities(&self) -> Capabilities;
+    /// スレッドや板の URL から、板の URL とスレッドのキーを得る。通信はしない
+    fn board_url_and_key(&self, url: &Url) -> Option<(Url, Option<u64>)>;
+    fn parse_thread_url(&self, client: &BbsClient, url: &Url) -> Option<Box<dyn Thread>>;
+    fn parse_board_url(&self, client: &BbsClient, url: &Url) -> Option<Box<dyn Board>>;
+}
+
+/// URL を判定する `BbsProvider` の一覧。先頭から順に試す
+pub struct ProviderRegistry {
+    providers: Vec<Arc<dyn BbsProvider>>,
+}
+
+impl Default for ProviderRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            providers: Vec::new(),
+        };
+        // 互換掲示板はどの URL にも当てはまりやすいので最後に試す。
+        // まちBBS はしたらばと同じ `/bbs/read.cgi/` を使うのでホストで先に見分ける
+        registry.register(Arc::new(CompatibleProvider));
+        registry.register(Arc::new(ShitarabaProvider));
+        registry.register(Arc::new(MachiProvider));
+        registry
+    }
+}
+
+impl ProviderRegistry {
+    /// 後から登録したものを先に試す。組み込みのものより優先するときに使う
+    pub fn register(&mut self, provider: Arc<dyn BbsProvider>) {
+        self.providers.insert(0, provider);
+    }
+
+    pub fn board_url_and_key(&self, url: &Url) -> Option<(Url, Option<u64>)> {
+        self.providers.iter().find_map(|x| x.board_url_and_key(url))
+    }
+
+    /// スレッドや板の URL の掲示板ソフトが対応している機能。掲示板の URL でなければ `None`
+    pub fn capabilities(&self, url: &Url) -> Option<Capabilities> {
+        self.providers
+            .iter()
+            .find(|x| x.board_url_and_key(url).is_some())
+            .map(|x| x.capabilities())
+    }
+
+    /// 作るスレッドや板には `client` を持たせる
+    pub fn parse_url(&self, client: &BbsClient, url: Url) -> Result<BbsUrl, Url> {
+        for provider in &self.providers {
+            if let Some(thread) = provider.parse_thread_url(client, &url) {
+                debug!("{} thread: {}", provider.name(), url);
+                return Ok(BbsUrl::Thread(url, thread));
+            }
+            if let Some(board) = provider.parse_board_url(client, &url) {
+                debug!("{} board: {}", provider.name(), url);
+                return Ok(BbsUrl::Board(url, board));
+            }
+        }
+        Err(url)
+    }
+
+    pub fn new_thread(&self, client: &BbsClient, url: &Url) -> Result<Box<dyn Thread>> {
+        self.providers
+            .iter()
+            .find_map(|x| x.parse_thread_url(client, url))
+            .ok_or_else(|| BbsError::InvalidUrl(url.to_string()))
+    }
+}
+
+static PROVIDERS: LazyLock<ProviderRegistry> = LazyLock::new(Default::default);
+
+pub fn capabilities(url: &Url) -> Option<Capabilities> {
+    PROVIDERS.capabilities(url)
+}
+
+/// 通信せずに、スレッドや板の URL から板の URL とスレッドのキーを得る
+pub fn board_url_and_key(url: &Url) -> Option<(Url, Option<u64>)> {
+    PROVIDERS.board_url_and_key(url)
+}
+
+pub fn parse_bbs_url(client: &BbsClient, url: Url) -> Result<BbsUrl, Url> {
+    PROVIDERS.parse_url(client, url)
+}
+
+pub fn new(client: &BbsClient, url: &Url) -> Result<Box<dyn Thread>> {
+    PROVIDERS.new_thread(client, url)
+}
@@ -0,0 +1,58 @@
+use super::html::{decode_entities, strip_tags};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Res {
+    pub number: u32,
+    pub name: String,
+    pub mail: String,
+    pub date: String,
+    pub id: Option<String>,
+    pub body: String,
+}
+
+/// `2025/06/08(日) 14:10:08.12 ID:abcd1234` を日付と ID に分ける
+pub fn split_date_id(date_id: &str) -> (String, Option<String>) {
+    match date_id.split_once(" ID:") {
+        Some((date, rest)) => (
+            date.trim().to_owned(),
+            rest.split_whitespace().next().map(str::to_owned),
+        ),
+        None => (date_id.trim().to_owned(), None),
+    }
+}
+
+pub fn parse_body(body: &str) -> String {
+    let body = body.strip_prefix(' ').unwrap_or(body);
+    let body = body.strip_suffix(' ').unwrap_or(body);
+    strip_tags(body)
+}
+
+pub fn parse_name(name: &str) -> String {
+    strip_tags(name).trim().to_owned()
+}
+
+/// `name<>mail<>date ID<>body<>title` 形式の 1 行を読む
+fn parse_dat_line(number: u32, line: &str) -> Option<Res> {
+    let mut fields = line.split("<>");
+    let name = fields.next()?;
+    let mail = fields.next()?;
+    let date_id = fields.next()?;
+    let body = fields.next()?;
+    let (date, id) = split_date_id(date_id);
+    Some(Res {
+        number,
+        name: parse_name(name),
+        mail: decode_entities(mail),
+        date,
+        id,
+        body: parse_body(body),
+    })
+}
+
+/// DAT 全体を読む。壊れた行は飛ばすが番号は行番号に合わせる
+pub fn parse_dat(dat: &str) -> Vec<Res> {
+    dat.lines()
+        .zip(1..)
+        .filter_map(|(line, number)| parse_dat_line(number, line))
+        .collect()
+}
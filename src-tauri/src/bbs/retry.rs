@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use tracing::info;
+
+use super::{
+    PostOutcome, Thread,
+    error::{BbsError, Result},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// 最初の書き込みを除いて何回まで再試行するか
+    pub max_retries: u32,
+    /// 待ち時間が示されないときの最初の待ち時間。再試行のたびに倍にする
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// 待ち時間の合計の上限。これを超えるなら諦める
+    pub budget: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(120),
+            budget: Duration::from_secs(300),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RetryReason {
+    RateLimited,
+    ServerError(StatusCode),
+}
+
+/// `attempt` 回目の書き込みが `reason` で失敗し、`wait` 後に再試行する
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryProgress {
+    pub attempt: u32,
+    pub wait: Duration,
+    pub reason: RetryReason,
+}
+
+/// 書き込みが掲示板に届いていないと分かる失敗か。
+/// 届いたか分からないものを送り直すと二重に書き込むので、連投規制と、
+/// 手前のサーバーが本文無しで返したものと、接続できなかったものに限る
+pub fn is_safe_to_resend(err: &BbsError) -> bool {
+    match err {
+        BbsError::RateLimited { .. } => true,
+        BbsError::Network(err) => err.is_connect(),
+        BbsError::HttpStatus {
+            status,
+            empty_body: true,
+            ..
+        } => matches!(
+            *status,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+        ),
+        // 書き込めなかった残りだけを送り直す
+        BbsError::PartiallyPosted { source, .. } => is_safe_to_resend(source),
+        _ => false,
+    }
+}
+
+/// 再試行すべき結果なら理由と、掲示板が示した待ち時間を返す
+fn retry_reason(result: &Result<PostOutcome>) -> Option<(RetryReason, Option<Duration>)> {
+    match result {
+        Ok(PostOutcome::RateLimited { retry_after }) => {
+            Some((RetryReason::RateLimited, *retry_after))
+        }
+        Err(err @ BbsError::HttpStatus { status, .. }) if is_safe_to_resend(err) => {
+            Some((RetryReason::ServerError(*status), None))
+        }
+        _ => None,
+    }
+}
+
+/// 連投規制やサーバーエラーで書き込めなかったら、待ってから書き込み直す
+pub async fn post_with_retry(
+    thread: &dyn Thread,
+    charset: &str,
+    name: &str,
+    email: &str,
+    msg: &str,
+    policy: &RetryPolicy,
+    on_retry: &(dyn Fn(RetryProgress) + Send + Sync),
+) -> Result<PostOutcome> {
+    let mut waited = Duration::ZERO;
+    let mut attempt = 1;
+    loop {
+        let result = thread.post(charset, name, email, msg).await;
+        let Some((reason, retry_after)) = retry_reason(&result) else {
+            return result;
+        };
+        let retry = attempt - 1;
+        // 掲示板が示した待ち時間は短くしない
+        let wait = retry_after.unwrap_or_else(|| policy.backoff(retry));
+        if retry >= policy.max_retries || waited + wait > policy.budget {
+            info!("giving up retrying: {:?}", reason);
+            return result;
+        }
+        on_retry(RetryProgress {
+            attempt,
+            wait,
+            reason,
+        });
+        tokio::time::sleep(wait).await;
+        waited += wait;
+        attempt += 1;
+    }
+}
@@ -1,25 +1,40 @@
-use anyhow::{Result, anyhow, bail};
-use encoding_rs::Encoding;
-use percent_encoding::{NON_ALPHANUMERIC, percent_encode};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use encoding_rs::EUC_JP;
 use regex::Regex;
-use reqwest::header::{CONTENT_TYPE, REFERER, USER_AGENT};
-use tracing::{debug, trace};
+use reqwest::header::{CONTENT_TYPE, REFERER};
+use tracing::debug;
 use url::Url;
 
-use super::{Thread, UA};
+use super::{
+    BbsError, BbsProvider, Board, Capabilities, PostOutcome, Res, Thread, ThreadReader,
+    board_settings::{BoardSettings, fetch_cached, parse_key_values},
+    client::{BbsClient, CheckStatus},
+    encoding::{decode_response, encode_form, unmappable_policy},
+    error::{Result, encoding_for_label},
+    html::decode_entities,
+    outcome::parse_post_response,
+    res::{parse_body, parse_name},
+    subject::{ThreadSummary, find_thread_by_title, read_cgi_thread_list},
+};
 
-pub fn parse_thread_url(thread_url: &Url) -> Option<Shitaraba> {
-    let origin = thread_url.origin().ascii_serialization();
+/// `/bbs/read.cgi/DIR/BBS/KEY/` を `(DIR, BBS, KEY)` にする
+fn parse_thread_path(thread_url: &Url) -> Option<(String, u64, u64)> {
     let c = Regex::new(r"^/bbs/read.cgi/(.+?)/(.+?)/(.+?)(:?/.*)?$")
         .unwrap()
         .captures(thread_url.path())?;
-    let dir = c.get(1).unwrap().as_str().to_string();
-    let bbs = c.get(2).unwrap().as_str().parse().ok()?;
-    let key = c.get(3).unwrap().as_str().parse().ok()?;
+    Some((
+        c.get(1).unwrap().as_str().to_string(),
+        c.get(2).unwrap().as_str().parse().ok()?,
+        c.get(3).unwrap().as_str().parse().ok()?,
+    ))
+}
+
+pub fn parse_thread_url(client: &BbsClient, thread_url: &Url) -> Option<Shitaraba> {
+    let origin = thread_url.origin().ascii_serialization();
+    let (dir, bbs, key) = parse_thread_path(thread_url)?;
     Some(Shitaraba {
-        origin,
-        dir,
-        bbs,
+        board: ShitarabaBoard::new(client.clone(), origin, dir, bbs),
         key,
     })
 }
@@ -34,90 +49,346 @@ pub fn parse_board_url(board_url: &Url) -> Option<(String, u64)> {
     ))
 }
 
-async fn fetch_subject_txt(origin: &str, dir: &str, bbs: u64) -> Result<String> {
+fn board_url(origin: &str, dir: &str, bbs: u64) -> Url {
+    Url::parse(&format!("{}/{}/{}/", origin, dir, bbs)).unwrap()
+}
+
+async fn fetch_subject_txt(
+    client: &BbsClient,
+    origin: &str,
+    dir: &str,
+    bbs: u64,
+) -> Result<String> {
     let subject_url = format!("{}/{}/{}/subject.txt", origin, dir, bbs);
-    Ok(reqwest::get(subject_url).await?.text().await?)
+    let resp = client.get(subject_url).send().await?.check_status()?;
+    decode_response(resp, EUC_JP).await
+}
+
+/// `KEY.cgi,TITLE(N)` 形式の subject.txt を読む
+pub(super) fn read_thread_list(subject_txt: &str) -> Vec<ThreadSummary> {
+    let mut threads = read_cgi_thread_list(subject_txt);
+    // したらばは最終行に先頭のスレッドをもう一度出力する
+    if threads.len() > 1 && threads.first().map(|x| x.key) == threads.last().map(|x| x.key) {
+        threads.pop();
+    }
+    threads
 }
 
-fn read_latest_thread(subject_txt: &str) -> Result<u64> {
-    Ok(subject_txt
-        .lines()
-        .next()
-        .ok_or_else(|| anyhow!("Empty subject.txt"))?
-        .split(".")
-        .next()
-        .ok_or_else(|| anyhow!("Empty subject.txt"))?
-        .parse()?)
+pub async fn fetch_thread_list(
+    client: &BbsClient,
+    origin: &str,
+    dir: &str,
+    bbs: u64,
+) -> Result<Vec<ThreadSummary>> {
+    let subject_txt = fetch_subject_txt(client, origin, dir, bbs).await?;
+    Ok(read_thread_list(&subject_txt))
 }
 
-async fn fetch_latest_thread(origin: &str, dir: &str, bbs: u64) -> Result<u64> {
-    let subject_txt = fetch_subject_txt(origin, dir, bbs).await?;
-    read_latest_thread(&subject_txt)
+/// setting.cgi の `KEY=VALUE` を読む。書き込みの上限は公開されていない
+pub(super) fn parse_setting_cgi(text: &str) -> BoardSettings {
+    let values = parse_key_values(text);
+    let string = |key: &str| values.get(key).filter(|x| !x.is_empty()).cloned();
+    BoardSettings {
+        title: string("BBS_TITLE"),
+        comment: string("BBS_COMMENT"),
+        noname_name: string("BBS_NONAME_NAME"),
+        thread_stop: values.get("BBS_THREAD_STOP").and_then(|x| x.parse().ok()),
+        ..Default::default()
+    }
 }
 
-pub async fn fetch_latest_thread_url(origin: &str, dir: &str, bbs: u64) -> Result<Url> {
-    let key = fetch_latest_thread(origin, dir, bbs)
-        .await
-        .inspect_err(|err| trace!("{:?}", err))?;
-    let thread_url = format!("{}/bbs/read.cgi/{}/{}/{}/", origin, dir, bbs, key);
-    Ok(Url::parse(&thread_url).unwrap())
+async fn fetch_setting_cgi(
+    client: &BbsClient,
+    origin: &str,
+    dir: &str,
+    bbs: u64,
+) -> Result<BoardSettings> {
+    let setting_url = format!("{}/bbs/api/setting.cgi/{}/{}/", origin, dir, bbs);
+    let resp = client.get(setting_url).send().await?.check_status()?;
+    Ok(parse_setting_cgi(&decode_response(resp, EUC_JP).await?))
 }
 
-fn charset_percent_encode(encoding: &'static Encoding, text: &str) -> String {
-    let (text, _, _) = encoding.encode(text);
-    percent_encode(&text, NON_ALPHANUMERIC).to_string()
+pub async fn fetch_board_settings(
+    client: &BbsClient,
+    origin: &str,
+    dir: &str,
+    bbs: u64,
+) -> Result<BoardSettings> {
+    let board_url = format!("{}/{}/{}/", origin, dir, bbs);
+    fetch_cached(&board_url, fetch_setting_cgi(client, origin, dir, bbs)).await
 }
 
-pub struct Shitaraba {
+/// rawmode.cgi の `num<>name<>mail<>date<>body<>title<>id` 形式を読む
+pub(super) fn parse_rawmode(text: &str) -> Vec<Res> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split("<>");
+            let number = fields.next()?.parse().ok()?;
+            let name = fields.next()?;
+            let mail = fields.next()?;
+            let date = fields.next()?;
+            let body = fields.next()?;
+            let _title = fields.next()?;
+            let id = fields.next().filter(|x| !x.is_empty());
+            Some(Res {
+                number,
+                name: parse_name(name),
+                mail: decode_entities(mail),
+                date: date.to_owned(),
+                id: id.map(str::to_owned),
+                body: parse_body(body),
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+pub struct ShitarabaBoard {
+    client: BbsClient,
     origin: String,
     dir: String,
     bbs: u64,
-    key: u64,
 }
 
-impl Shitaraba {
-    pub async fn new(url: &Url) -> Result<Self> {
-        if let Some(site) = parse_thread_url(url) {
-            return Ok(site);
+impl ShitarabaBoard {
+    pub fn new(client: BbsClient, origin: String, dir: String, bbs: u64) -> Self {
+        Self {
+            client,
+            origin,
+            dir,
+            bbs,
         }
-        bail!("Invalid URL: {}", url);
     }
-}
 
-#[async_trait::async_trait]
-impl Thread for Shitaraba {
-    async fn post(&self, charset: &str, name: &str, email: &str, msg: &str) -> Result<()> {
-        let encoding = Encoding::for_label(charset.as_bytes()).unwrap();
-        let name = charset_percent_encode(encoding, name);
-        let email = charset_percent_encode(encoding, email);
-        let msg = charset_percent_encode(encoding, msg);
-        let resp = reqwest::Client::new()
-            .post(format!(
-                "{}/bbs/write.cgi/{}/{}/{}/",
-                self.origin, self.dir, self.bbs, self.key
-            ))
+    async fn send_form(
+        &self,
+        charset: &str,
+        url: String,
+        referer: String,
+        form: &[(&str, &str)],
+    ) -> Result<PostOutcome> {
+        let encoding = encoding_for_label(charset)?;
+        let policy = unmappable_policy(&self.board_url());
+        let body = encode_form(encoding, &policy, form)?;
+        let resp = self
+            .client
+            .post(url)
             .header(
                 CONTENT_TYPE,
                 format!("application/x-www-form-urlencoded; charset={}", charset),
             )
-            .header(
-                REFERER,
+            .header(REFERER, referer)
+            .body(body)
+            .send()
+            .await?
+            .check_status()?;
+        let text = decode_response(resp, encoding).await?;
+        debug!("post resp: {}", text);
+        Ok(parse_post_response(&text))
+    }
+}
+
+#[async_trait::async_trait]
+impl Board for ShitarabaBoard {
+    fn board_url(&self) -> Url {
+        board_url(&self.origin, &self.dir, self.bbs)
+    }
+
+    fn thread(&self, key: u64) -> Box<dyn Thread> {
+        Box::new(Shitaraba {
+            board: self.clone(),
+            key,
+        })
+    }
+
+    fn thread_url(&self, key: u64) -> Url {
+        let thread_url = format!(
+            "{}/bbs/read.cgi/{}/{}/{}/",
+            self.origin, self.dir, self.bbs, key
+        );
+        Url::parse(&thread_url).unwrap()
+    }
+
+    async fn board_settings(&self) -> Result<BoardSettings> {
+        fetch_board_settings(&self.client, &self.origin, &self.dir, self.bbs).await
+    }
+
+    async fn fetch_thread_list(&self) -> Result<Vec<ThreadSummary>> {
+        fetch_thread_list(&self.client, &self.origin, &self.dir, self.bbs).await
+    }
+
+    async fn create_thread(
+        &self,
+        charset: &str,
+        subject: &str,
+        name: &str,
+        email: &str,
+        msg: &str,
+    ) -> Result<Url> {
+        let encoding = encoding_for_label(charset)?;
+        match self.board_settings().await {
+            Ok(settings) => {
+                settings.validate_subject(encoding, subject)?;
+                settings.validate(encoding, name, email, msg)?;
+            }
+            Err(err) => debug!("no setting.cgi: {:?}", err),
+        }
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            .to_string();
+        let bbs = self.bbs.to_string();
+        let outcome = self
+            .send_form(
+                charset,
                 format!(
-                    "{}/bbs/read.cgi/{}/{}/{}/",
-                    self.origin, self.dir, self.bbs, self.key
+                    "{}/bbs/write.cgi/{}/{}/new/",
+                    self.origin, self.dir, self.bbs
                 ),
+                format!("{}/{}/{}/", self.origin, self.dir, self.bbs),
+                &[
+                    ("DIR", &self.dir),
+                    ("BBS", &bbs),
+                    ("TIME", &time),
+                    ("SUBJECT", subject),
+                    ("NAME", name),
+                    ("MAIL", email),
+                    ("MESSAGE", msg),
+                    ("submit", "新規スレッド作成"),
+                ],
             )
-            .header(USER_AGENT, UA)
-            .body(format!(
-                "BBS={}&KEY={}&DIR={}&NAME={}&MAIL={}&MESSAGE={}",
-                self.bbs, self.key, self.dir, name, email, msg
-            ))
+            .await?;
+        outcome.into_result()?;
+        let threads = self.fetch_thread_list().await?;
+        let key = find_thread_by_title(&threads, subject).ok_or_else(|| BbsError::ParseError {
+            context: "subject.txt".to_owned(),
+            message: format!("立てたスレッドが見つかりません: {}", subject),
+        })?;
+        Ok(self.thread_url(key))
+    }
+}
+
+pub struct ShitarabaProvider;
+
+impl BbsProvider for ShitarabaProvider {
+    fn name(&self) -> &'static str {
+        "したらば"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            create_thread: true,
+            board_settings: true,
+        }
+    }
+
+    fn board_url_and_key(&self, url: &Url) -> Option<(Url, Option<u64>)> {
+        let (dir, bbs, key) = match parse_thread_path(url) {
+            Some((dir, bbs, key)) => (dir, bbs, Some(key)),
+            None => {
+                let (dir, bbs) = parse_board_url(url)?;
+                (dir, bbs, None)
+            }
+        };
+        Some((
+            board_url(&url.origin().ascii_serialization(), &dir, bbs),
+            key,
+        ))
+    }
+
+    fn parse_thread_url(&self, client: &BbsClient, url: &Url) -> Option<Box<dyn Thread>> {
+        Some(Box::new(parse_thread_url(client, url)?))
+    }
+
+    fn parse_board_url(&self, client: &BbsClient, url: &Url) -> Option<Box<dyn Board>> {
+        let (dir, bbs) = parse_board_url(url)?;
+        let origin = url.origin().ascii_serialization();
+        Some(Box::new(ShitarabaBoard::new(
+            client.clone(),
+            origin,
+            dir,
+            bbs,
+        )))
+    }
+}
+
+pub struct Shitaraba {
+    board: ShitarabaBoard,
+    key: u64,
+}
+
+#[async_trait::async_trait]
+impl Thread for Shitaraba {
+    fn key(&self) -> u64 {
+        self.key
+    }
+
+    fn board(&self) -> Box<dyn Board> {
+        Box::new(self.board.clone())
+    }
+
+    async fn post(&self, charset: &str, name: &str, email: &str, msg: &str) -> Result<PostOutcome> {
+        let encoding = encoding_for_label(charset)?;
+        match self.board.board_settings().await {
+            Ok(settings) => settings.validate(encoding, name, email, msg)?,
+            Err(err) => debug!("no setting.cgi: {:?}", err),
+        }
+        let board = &self.board;
+        let bbs = board.bbs.to_string();
+        let key = self.key.to_string();
+        board
+            .send_form(
+                charset,
+                format!(
+                    "{}/bbs/write.cgi/{}/{}/{}/",
+                    board.origin, board.dir, board.bbs, self.key
+                ),
+                board.thread_url(self.key).to_string(),
+                &[
+                    ("BBS", &bbs),
+                    ("KEY", &key),
+                    ("DIR", &board.dir),
+                    ("NAME", name),
+                    ("MAIL", email),
+                    ("MESSAGE", msg),
+                ],
+            )
+            .await
+    }
+}
+
+#[async_trait::async_trait]
+impl ThreadReader for Shitaraba {
+    async fn read(&self, charset: &str, from: u32) -> Result<Vec<Res>> {
+        let encoding = encoding_for_label(charset)?;
+        let rawmode_url = format!(
+            "{}/bbs/rawmode.cgi/{}/{}/{}/{}-",
+            self.board.origin,
+            self.board.dir,
+            self.board.bbs,
+            self.key,
+            from.max(1)
+        );
+        let resp = self
+            .board
+            .client
+            .get(rawmode_url)
             .send()
             .await?
-            .error_for_status()?;
-        let bytes = resp.bytes().await?;
-        let text = Encoding::for_label(b"euc-jp").unwrap().decode(&bytes).0;
-        debug!("post resp: {}", text.to_string());
-        Ok(())
+            .check_status()?;
+        // スレッドが無い・停止している場合は ERROR ヘッダーで知らされる
+        if let Some(error) = resp.headers().get("ERROR") {
+            let error = String::from_utf8_lossy(error.as_bytes());
+            return Err(match error.as_ref() {
+                "STORAGE IN" => BbsError::ThreadStopped,
+                "BBS NOT FOUND" | "KEY NOT FOUND" | "THREAD NOT FOUND" => {
+                    BbsError::InvalidUrl(self.board.thread_url(self.key).to_string())
+                }
+                _ => BbsError::Rejected(error.into_owned()),
+            });
+        }
+        let text = decode_response(resp, encoding).await?;
+        Ok(parse_rawmode(&text))
     }
 }
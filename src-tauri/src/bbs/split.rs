@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use encoding_rs::Encoding;
+use tracing::info;
+
+use super::{
+    BoardSettings, Thread,
+    board_settings::encoded_len,
+    encoding::clusters,
+    error::{BbsError, Result},
+    next_thread::{NextThread, post_following_next_thread},
+    retry::{RetryPolicy, RetryProgress},
+};
+
+/// 一行が上限を超えるなら、文字の途中で切らないように分ける
+fn cut_line<'a>(encoding: &'static Encoding, line: &'a str, max_bytes: usize) -> Vec<&'a str> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+    for cluster in clusters(line) {
+        let next = end + cluster.len();
+        if end > start && encoded_len(encoding, &line[start..next]) > max_bytes {
+            chunks.push(&line[start..end]);
+            start = end;
+        }
+        end = next;
+    }
+    chunks.push(&line[start..end]);
+    chunks
+}
+
+/// 行単位で詰め込む。各部分の先頭に `header_bytes` バイトの番号の行が付く
+fn pack(
+    encoding: &'static Encoding,
+    settings: &BoardSettings,
+    msg: &str,
+    header_bytes: usize,
+) -> Vec<String> {
+    let max_bytes = settings
+        .max_message_bytes
+        .map(|x| x.saturating_sub(header_bytes).max(1));
+    let max_lines = settings.max_lines.map(|x| x.saturating_sub(1).max(1));
+    let fits = |text: &str, lines: usize| {
+        max_bytes.is_none_or(|max| encoded_len(encoding, text) <= max)
+            && max_lines.is_none_or(|max| lines <= max)
+    };
+
+    let mut parts = Vec::new();
+    let mut current: Option<(String, usize)> = None;
+    let lines = msg.split('\n').flat_map(|line| match max_bytes {
+        Some(max) => cut_line(encoding, line, max),
+        None => vec![line],
+    });
+    for line in lines {
+        if let Some((text, count)) = &mut current {
+            let joined = format!("{}\n{}", text, line);
+            if fits(&joined, *count + 1) {
+                *text = joined;
+                *count += 1;
+                continue;
+            }
+            parts.extend(current.take().map(|x| x.0));
+        }
+        current = Some((line.to_owned(), 1));
+    }
+    parts.extend(current.map(|x| x.0));
+    parts
+}
+
+fn header(index: usize, total: usize) -> String {
+    format!("({}/{})\n", index, total)
+}
+
+/// 板の上限に収まるよう行の区切りで分け、`(1/3)` のように番号を付ける。収まるならそのまま返す
+pub fn split_message(
+    encoding: &'static Encoding,
+    settings: &BoardSettings,
+    msg: &str,
+) -> Vec<String> {
+    if settings.validate(encoding, "", "", msg).is_ok() {
+        return vec![msg.to_owned()];
+    }
+    // 番号の桁数で使えるバイト数が変わるので、件数が落ち着くまで詰め直す
+    let mut total = 2;
+    loop {
+        let parts = pack(encoding, settings, msg, header(total, total).len());
+        if header(parts.len(), parts.len()).len() <= header(total, total).len() {
+            let total = parts.len();
+            return parts
+                .into_iter()
+                .enumerate()
+                .map(|(i, part)| format!("{}{}", header(i + 1, total), part))
+                .collect();
+        }
+        total = parts.len();
+    }
+}
+
+/// `posted` 件目までは書き込めたものとしてエラーを包む
+fn partial(posted: usize, total: usize, source: BbsError) -> BbsError {
+    if posted == 0 {
+        return source;
+    }
+    BbsError::PartiallyPosted {
+        posted,
+        total,
+        source: Box::new(source),
+    }
+}
+
+/// 分けた本文を `interval` ずつ空けて順に書き込む。`skip` 件目までは書き込み済みとして飛ばす。
+/// 1 件書き込むたびに書き込み済みの件数で `on_posted` を呼ぶ。
+/// 途中で次スレに移動した場合は移動先を返す
+#[allow(clippy::too_many_arguments)]
+pub async fn post_parts(
+    thread: &dyn Thread,
+    charset: &str,
+    name: &str,
+    email: &str,
+    parts: &[String],
+    skip: usize,
+    interval: Duration,
+    retry: &RetryPolicy,
+    on_retry: &(dyn Fn(RetryProgress) + Send + Sync),
+    on_posted: &(dyn Fn(usize) + Send + Sync),
+) -> Result<Option<NextThread>> {
+    let total = parts.len();
+    let mut moved: Option<(Box<dyn Thread>, NextThread)> = None;
+    for (i, part) in parts.iter().enumerate().skip(skip) {
+        if i > skip {
+            tokio::time::sleep(interval).await;
+        }
+        let current = moved.as_ref().map_or(thread, |(x, _)| x.as_ref());
+        let (outcome, next) =
+            post_following_next_thread(current, charset, name, email, part, retry, on_retry)
+                .await
+                .map_err(|err| partial(i, total, err))?;
+        outcome
+            .into_result()
+            .map_err(|err| partial(i, total, err))?;
+        info!("posted part {}/{}", i + 1, total);
+        on_posted(i + 1);
+        if let Some(next) = next {
+            // 残りも移動先に書き込む
+            moved = Some((current.board().thread(next.key), next));
+        }
+    }
+    Ok(moved.map(|(_, next)| next))
+}
@@ -0,0 +1,53 @@
+use regex::Regex;
+
+use super::html::decode_entities;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadSummary {
+    pub key: u64,
+    pub title: String,
+    pub res_count: u32,
+}
+
+/// `TITLE (N)` をタイトルとレス数に分ける
+pub fn split_title_res_count(text: &str) -> Option<(String, u32)> {
+    let c = Regex::new(r"^(.*?)\s*\(([0-9]+)\)\s*$")
+        .unwrap()
+        .captures(text)?;
+    Some((decode_entities(&c[1]), c[2].parse().ok()?))
+}
+
+/// したらばとまちBBS の `KEY.cgi,TITLE(N)` 形式の subject.txt を読む
+pub fn read_cgi_thread_list(subject_txt: &str) -> Vec<ThreadSummary> {
+    subject_txt
+        .lines()
+        .filter_map(|line| {
+            let (file, rest) = line.split_once(',')?;
+            let key = file.strip_suffix(".cgi")?.parse().ok()?;
+            let (title, res_count) = split_title_res_count(rest)?;
+            Some(ThreadSummary {
+                key,
+                title,
+                res_count,
+            })
+        })
+        .collect()
+}
+
+/// 掲示板は空白を詰めたり記号を実体参照にしたりするので、揃えてから比べる
+fn normalize_title(title: &str) -> String {
+    decode_entities(title)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 立てたスレッドをタイトルから探す。同じタイトルなら新しい方
+pub fn find_thread_by_title(threads: &[ThreadSummary], title: &str) -> Option<u64> {
+    let title = normalize_title(title);
+    threads
+        .iter()
+        .filter(|x| normalize_title(&x.title) == title)
+        .map(|x| x.key)
+        .max()
+}
@@ -1,91 +1,1448 @@
 use url::Url;
 
-use crate::bbs::BbsUrl;
+use crate::bbs::{BbsUrl, BoardSettings, PostOutcome, Res, ThreadSummary};
+
+/// 既定の設定のクライアント
+fn client() -> super::BbsClient {
+    super::BbsClient::new(&super::BbsClientConfig::default()).unwrap()
+}
 
 struct EmptyThread;
 
+#[async_trait::async_trait]
+impl super::ThreadReader for EmptyThread {
+    async fn read(&self, _charset: &str, _from: u32) -> super::Result<Vec<Res>> {
+        Ok(Vec::new())
+    }
+}
+
+struct EmptyBoard;
+
+#[async_trait::async_trait]
+impl super::Board for EmptyBoard {
+    fn board_url(&self) -> Url {
+        Url::parse("https://example.com/progre/").unwrap()
+    }
+
+    fn thread_url(&self, key: u64) -> Url {
+        Url::parse(&format!(
+            "https://example.com/test/read.cgi/progre/{}/",
+            key
+        ))
+        .unwrap()
+    }
+
+    fn thread(&self, _key: u64) -> Box<dyn super::Thread> {
+        Box::new(EmptyThread)
+    }
+
+    async fn board_settings(&self) -> super::Result<BoardSettings> {
+        Ok(BoardSettings::default())
+    }
+
+    async fn fetch_thread_list(&self) -> super::Result<Vec<ThreadSummary>> {
+        Ok(Vec::new())
+    }
+
+    async fn create_thread(
+        &self,
+        _charset: &str,
+        _subject: &str,
+        _name: &str,
+        _email: &str,
+        _msg: &str,
+    ) -> super::Result<Url> {
+        Ok(self.thread_url(0))
+    }
+}
+
 #[async_trait::async_trait]
 impl super::Thread for EmptyThread {
+    fn key(&self) -> u64 {
+        0
+    }
+
+    fn board(&self) -> Box<dyn super::Board> {
+        Box::new(EmptyBoard)
+    }
+
     async fn post(
         &self,
         _charset: &str,
         _name: &str,
         _email: &str,
         _msg: &str,
-    ) -> anyhow::Result<()> {
-        Ok(())
+    ) -> super::Result<PostOutcome> {
+        Ok(PostOutcome::Accepted)
     }
 }
 
 #[test]
 fn test_parse_bbs_url() {
-    let data: [(&str, Option<BbsUrl>); _] = [
+    // 板の URL とスレッドのキー。板の URL ならキーは無い
+    let data = [
         (
             "https://jbbs.shitaraba.net/bbs/read.cgi/radio/22607/1484488601/l50",
-            Some(BbsUrl::ProbablyShitarabaThread(
-                Url::parse("https://jbbs.shitaraba.net/bbs/read.cgi/radio/22607/1484488601/l50")
-                    .unwrap(),
-                Box::new(EmptyThread),
-            )),
+            Some(("https://jbbs.shitaraba.net/radio/22607/", Some(1484488601))),
         ),
         (
             "https://jbbs.shitaraba.net/radio/22607/",
-            Some(BbsUrl::ProbablyShitarabaBoard(
-                Url::parse("https://jbbs.shitaraba.net/radio/22607/").unwrap(),
-                "radio".to_string(),
-                22607,
-            )),
+            Some(("https://jbbs.shitaraba.net/radio/22607/", None)),
         ),
         (
             "https://bbs.jpnkn.com/test/read.cgi/progre/1749359408/l50",
-            Some(BbsUrl::ProbablyCompatibleThread(
-                Url::parse("https://bbs.jpnkn.com/test/read.cgi/progre/1749359408/l50").unwrap(),
-                Box::new(EmptyThread),
-            )),
+            Some(("https://bbs.jpnkn.com/progre/", Some(1749359408))),
         ),
         (
             "https://bbs.jpnkn.com/progre/",
-            Some(BbsUrl::MaybeCompatibleBoard(
-                Url::parse("https://bbs.jpnkn.com/progre/").unwrap(),
-                "progre".to_string(),
-            )),
+            Some(("https://bbs.jpnkn.com/progre/", None)),
+        ),
+        // まちBBS もしたらばと同じ /bbs/read.cgi/ を使う
+        (
+            "https://machi.to/bbs/read.cgi/tawara/1700000000/l50",
+            Some(("https://machi.to/tawara/", Some(1700000000))),
+        ),
+        (
+            "https://machi.to/tawara/",
+            Some(("https://machi.to/tawara/", None)),
         ),
         ("https://example.com/", None),
     ];
 
     for (url_str, expected) in data {
         let url = Url::parse(url_str).unwrap();
-        let result = super::parse_bbs_url(url);
-        match (result, expected) {
-            (
-                Ok(BbsUrl::ProbablyShitarabaThread(u1, _)),
-                Some(BbsUrl::ProbablyShitarabaThread(u2, _)),
-            ) => {
-                assert_eq!(u1, u2);
+        // 通信しない判定も同じ結果になる
+        let located = super::board_url_and_key(&url);
+        assert_eq!(
+            located
+                .as_ref()
+                .map(|(board_url, key)| (board_url.as_str(), *key)),
+            expected,
+            "Mismatched location for URL: {}",
+            url_str
+        );
+        let result = match super::parse_bbs_url(&client(), url) {
+            Ok(BbsUrl::Thread(url, thread)) => {
+                assert_eq!(url.as_str(), url_str);
+                let board = thread.board();
+                // スレッドの URL は末尾の `l50` などを落としたもの
+                assert_eq!(
+                    board.thread_url(thread.key()).as_str(),
+                    url_str.trim_end_matches("l50"),
+                );
+                Some((board.board_url().to_string(), Some(thread.key())))
             }
-            (
-                Ok(BbsUrl::ProbablyShitarabaBoard(u1, d1, b1)),
-                Some(BbsUrl::ProbablyShitarabaBoard(u2, d2, b2)),
-            ) => {
-                assert_eq!(u1, u2);
-                assert_eq!(d1, d2);
-                assert_eq!(b1, b2);
+            Ok(BbsUrl::Board(url, board)) => {
+                assert_eq!(url.as_str(), url_str);
+                Some((board.board_url().to_string(), None))
             }
-            (
-                Ok(BbsUrl::ProbablyCompatibleThread(u1, _)),
-                Some(BbsUrl::ProbablyCompatibleThread(u2, _)),
-            ) => {
-                assert_eq!(u1, u2);
+            Err(_) => None,
+        };
+        assert_eq!(
+            result
+                .as_ref()
+                .map(|(board_url, key)| (board_url.as_str(), *key)),
+            expected,
+            "Mismatched result for URL: {}",
+            url_str
+        );
+    }
+}
+
+#[test]
+fn test_register_provider() {
+    use std::sync::Arc;
+
+    use super::{BbsClient, BbsProvider, Capabilities, capabilities, provider::ProviderRegistry};
+
+    struct ExampleProvider;
+
+    impl BbsProvider for ExampleProvider {
+        fn name(&self) -> &'static str {
+            "example"
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities {
+                create_thread: false,
+                board_settings: false,
             }
-            (
-                Ok(BbsUrl::MaybeCompatibleBoard(u1, b1)),
-                Some(BbsUrl::MaybeCompatibleBoard(u2, b2)),
-            ) => {
-                assert_eq!(u1, u2);
-                assert_eq!(b1, b2);
+        }
+
+        fn board_url_and_key(&self, url: &Url) -> Option<(Url, Option<u64>)> {
+            (url.host_str() == Some("example.com"))
+                .then(|| (Url::parse("https://example.com/progre/").unwrap(), Some(0)))
+        }
+
+        fn parse_thread_url(
+            &self,
+            _client: &BbsClient,
+            url: &Url,
+        ) -> Option<Box<dyn super::Thread>> {
+            (url.host_str() == Some("example.com")).then(|| Box::new(EmptyThread) as _)
+        }
+
+        fn parse_board_url(
+            &self,
+            _client: &BbsClient,
+            _url: &Url,
+        ) -> Option<Box<dyn super::Board>> {
+            None
+        }
+    }
+
+    let mut registry = ProviderRegistry::default();
+    let url = Url::parse("https://example.com/test/read.cgi/progre/1749359408/").unwrap();
+    assert_eq!(
+        registry.new_thread(&client(), &url).unwrap().key(),
+        1749359408
+    );
+
+    registry.register(Arc::new(ExampleProvider));
+    assert_eq!(registry.board_url_and_key(&url).unwrap().1, Some(0));
+    assert!(!registry.capabilities(&url).unwrap().create_thread);
+    let thread = registry.new_thread(&client(), &url).unwrap();
+    assert_eq!(thread.key(), 0);
+    assert_eq!(
+        thread.board().board_url().as_str(),
+        "https://example.com/progre/"
+    );
+    // 他のホストは組み込みのものが扱う
+    let url = Url::parse("https://bbs.jpnkn.com/progre/").unwrap();
+    let Ok(BbsUrl::Board(_, board)) = registry.parse_url(&client(), url) else {
+        panic!("not a board");
+    };
+    assert_eq!(board.board_url().as_str(), "https://bbs.jpnkn.com/progre/");
+    // 組み込みの掲示板ソフトが対応している機能
+    let machi = Url::parse("https://tohoku.machi.to/bbs/read.cgi/tohoku/1/").unwrap();
+    assert_eq!(
+        capabilities(&machi),
+        Some(Capabilities {
+            create_thread: false,
+            board_settings: false,
+        })
+    );
+    let shitaraba = Url::parse("https://jbbs.shitaraba.net/radio/22607/").unwrap();
+    assert!(capabilities(&shitaraba).unwrap().create_thread);
+}
+
+#[test]
+fn test_parse_post_response() {
+    use std::time::Duration;
+
+    use super::outcome::parse_post_response;
+
+    let data = [
+        (
+            include_str!("fixtures/compatible_accepted.html"),
+            PostOutcome::Accepted,
+        ),
+        (
+            include_str!("fixtures/compatible_error.html"),
+            PostOutcome::Rejected {
+                reason: "本文がありません！".to_owned(),
+            },
+        ),
+        (
+            include_str!("fixtures/compatible_banned.html"),
+            PostOutcome::Rejected {
+                reason: "規制中です！！(ホスト)".to_owned(),
+            },
+        ),
+        (
+            include_str!("fixtures/compatible_confirmation.html"),
+            PostOutcome::ConfirmationRequired,
+        ),
+        (
+            include_str!("fixtures/compatible_rate_limited.html"),
+            PostOutcome::RateLimited {
+                retry_after: Some(Duration::from_secs(60)),
+            },
+        ),
+        (
+            include_str!("fixtures/compatible_thread_stopped.html"),
+            PostOutcome::ThreadStopped,
+        ),
+        (
+            include_str!("fixtures/shitaraba_accepted.html"),
+            PostOutcome::Accepted,
+        ),
+        (
+            include_str!("fixtures/shitaraba_error.html"),
+            PostOutcome::Rejected {
+                reason: "本文がありません".to_owned(),
+            },
+        ),
+        (
+            include_str!("fixtures/shitaraba_rate_limited.html"),
+            PostOutcome::RateLimited { retry_after: None },
+        ),
+        (
+            include_str!("fixtures/shitaraba_thread_stopped.html"),
+            PostOutcome::ThreadStopped,
+        ),
+        (
+            "<html><head><title>ＥＲＲＯＲ！</title></head><!-- 2ch_X:error --><body>\
+             ERROR: 本文が長すぎます！(2048バイトを超えました)</body></html>",
+            PostOutcome::Rejected {
+                reason: "本文が長すぎます！(2048バイトを超えました)".to_owned(),
+            },
+        ),
+        (
+            "<html><head><title>ＥＲＲＯＲ！</title></head><!-- 2ch_X:error --><body>\
+             ERROR: このスレッドは1000を超えました。</body></html>",
+            PostOutcome::ThreadStopped,
+        ),
+        (
+            "<html><head><title>お茶でも飲みましょう。</title></head>\
+             <!-- 2ch_X:false --><body>書きこみが終わりました。</body></html>",
+            PostOutcome::Accepted,
+        ),
+        (
+            "<html><body>maintenance</body></html>",
+            PostOutcome::Unknown {
+                html: "<html><body>maintenance</body></html>".to_owned(),
+            },
+        ),
+    ];
+
+    for (html, expected) in data {
+        assert_eq!(parse_post_response(html), expected);
+    }
+}
+
+#[test]
+fn test_read_confirmation_inputs() {
+    let html = include_str!("fixtures/compatible_confirmation.html");
+    let inputs = super::compatible::read_confirmation_inputs(html);
+    assert_eq!(
+        inputs,
+        [
+            ("subject", ""),
+            ("FROM", ""),
+            ("mail", "sage"),
+            ("MESSAGE", "テスト"),
+            ("bbs", "progre"),
+            ("time", "1749359408"),
+            ("key", "1749359408"),
+            ("feature", "confirmed:a1b2c3d4"),
+            ("submit", "上記全てを承諾して書き込む"),
+        ]
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+    );
+}
+
+#[test]
+fn test_cookie_jar() {
+    use reqwest::{cookie::CookieStore, header::HeaderValue};
+
+    use super::cookie_jar::CookieJar;
+
+    let path = std::env::temp_dir().join(format!("cookie_jar_{}.json", std::process::id()));
+    let url = Url::parse("https://bbs.jpnkn.com/test/bbs.cgi").unwrap();
+    let other = Url::parse("https://jbbs.shitaraba.net/bbs/write.cgi").unwrap();
+
+    let jar = CookieJar::load(Some(path.clone()));
+    jar.set("https://bbs.jpnkn.com", "NAME", r#""""#);
+    let set_cookies = [
+        HeaderValue::from_static("PON=abc123; path=/; HttpOnly"),
+        HeaderValue::from_static("yuki=akari; Max-Age=86400"),
+    ];
+    jar.set_cookies(&mut set_cookies.iter(), &url);
+    // Path 属性が無ければ /test に送る。パスが長いものが先
+    assert_eq!(
+        jar.cookies(&url).unwrap(),
+        r#"yuki=akari; NAME=""; PON=abc123"#
+    );
+    let top = Url::parse("https://bbs.jpnkn.com/progre/").unwrap();
+    assert_eq!(jar.cookies(&top).unwrap(), r#"NAME=""; PON=abc123"#);
+    assert!(jar.cookies(&other).is_none());
+    // 書き出しを待つ
+    drop(jar);
+
+    // 再読み込みしても残り、Max-Age=0 や過去の Expires で消える
+    let jar = CookieJar::load(Some(path.clone()));
+    let set_cookies = [
+        HeaderValue::from_static("yuki=; Max-Age=0"),
+        HeaderValue::from_static("PON=; path=/; Expires=Thu, 01 Jan 1970 00:00:00 GMT"),
+    ];
+    jar.set_cookies(&mut set_cookies.iter(), &url);
+    assert_eq!(jar.cookies(&url).unwrap(), r#"NAME="""#);
+
+    // Domain 属性があれば下位のドメインにも送り、関係ないドメインには設定させない
+    let sub = Url::parse("https://sub.shitaraba.net/bbs/write.cgi").unwrap();
+    let set_cookies = [
+        HeaderValue::from_static("a=1; Domain=.shitaraba.net; path=/"),
+        HeaderValue::from_static("b=2; Domain=example.com; path=/"),
+    ];
+    jar.set_cookies(&mut set_cookies.iter(), &other);
+    assert_eq!(jar.cookies(&sub).unwrap(), "a=1");
+    assert_eq!(jar.cookies(&other).unwrap(), "a=1");
+    assert!(
+        jar.cookies(&Url::parse("https://example.com/").unwrap())
+            .is_none()
+    );
+    drop(jar);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_parse_dat() {
+    use super::res::parse_dat;
+
+    let dat = include_str!("fixtures/compatible.dat");
+    assert_eq!(
+        parse_dat(dat),
+        [
+            Res {
+                number: 1,
+                name: "名無しさん".to_owned(),
+                mail: "sage".to_owned(),
+                date: "2025/06/08(日) 14:10:08.12".to_owned(),
+                id: Some("abcd1234".to_owned()),
+                body: "配信の連絡スレです\nよろしく & どうぞ".to_owned(),
+            },
+            Res {
+                number: 2,
+                name: "ぷろぐれ ◆Trip12345".to_owned(),
+                mail: "".to_owned(),
+                date: "2025/06/08(日) 14:11:00.00".to_owned(),
+                id: Some("efgh5678".to_owned()),
+                body: ">>1\n了解 😀".to_owned(),
+            },
+            Res {
+                number: 3,
+                name: "あぼーん".to_owned(),
+                mail: "あぼーん".to_owned(),
+                date: "あぼーん".to_owned(),
+                id: None,
+                body: "あぼーん".to_owned(),
+            },
+            Res {
+                number: 5,
+                name: "名無しさん".to_owned(),
+                mail: "".to_owned(),
+                date: "2025/06/08(日) 14:12:00.00".to_owned(),
+                id: None,
+                body: "最後".to_owned(),
+            },
+        ]
+    );
+}
+
+/// 決まった応答を順に返すだけの HTTP サーバー。受け取ったリクエストを返す
+async fn serve_stub(responses: Vec<Vec<u8>>) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let origin = format!("http://{}", listener.local_addr().unwrap());
+    let handle = tokio::spawn(async move {
+        let mut requests = Vec::new();
+        for response in responses {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = Vec::new();
+            let mut chunk = [0; 4096];
+            let header_end = loop {
+                let n = stream.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+                if let Some(pos) = buf.windows(4).position(|x| x == b"\r\n\r\n") {
+                    break pos + 4;
+                }
+            };
+            let header = String::from_utf8_lossy(&buf[..header_end]).into_owned();
+            let content_length = header
+                .lines()
+                .find_map(|x| {
+                    x.to_ascii_lowercase()
+                        .strip_prefix("content-length:")
+                        .map(|x| x.trim().parse::<usize>().unwrap())
+                })
+                .unwrap_or(0);
+            while buf.len() < header_end + content_length {
+                let n = stream.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
             }
-            (Err(_), None) => {}
-            _ => panic!("Mismatched result for URL: {}", url_str),
+            requests.push(String::from_utf8_lossy(&buf).into_owned());
+            stream.write_all(&response).await.unwrap();
+            stream.shutdown().await.unwrap();
         }
+        requests
+    });
+    (origin, handle)
+}
+
+fn stub_response(status: &str, headers: &[(&str, &str)], body: &[u8]) -> Vec<u8> {
+    let mut response = format!("HTTP/1.1 {}\r\nConnection: close\r\n", status);
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str(&format!("Content-Length: {}\r\n\r\n", body.len()));
+    let mut response = response.into_bytes();
+    response.extend_from_slice(body);
+    response
+}
+
+#[tokio::test]
+async fn test_read_shitaraba() {
+    let rawmode = encoding_rs::EUC_JP
+        .encode(include_str!("fixtures/shitaraba_rawmode.txt"))
+        .0
+        .into_owned();
+    let (origin, server) = serve_stub(vec![
+        stub_response("200 OK", &[], &rawmode),
+        stub_response("200 OK", &[("ERROR", "KEY NOT FOUND")], b""),
+    ])
+    .await;
+    let url = Url::parse(&format!("{}/bbs/read.cgi/radio/22607/1484488601/", origin)).unwrap();
+    let reader = super::new(&client(), &url).unwrap();
+
+    let responses = reader.read("euc-jp", 5).await.unwrap();
+    assert_eq!(
+        responses,
+        [
+            Res {
+                number: 5,
+                name: "名無しさん".to_owned(),
+                mail: "sage".to_owned(),
+                date: "2025/06/08(日) 14:10:08".to_owned(),
+                id: Some("AbCdEfGh".to_owned()),
+                body: "次の配信は\n21時から".to_owned(),
+            },
+            Res {
+                number: 6,
+                name: "ぷろぐれ".to_owned(),
+                mail: "".to_owned(),
+                date: "2025/06/08(日) 14:11:00".to_owned(),
+                id: None,
+                body: ">>5 了解".to_owned(),
+            },
+        ]
+    );
+    let err = reader.read("euc-jp", 7).await.unwrap_err();
+    assert!(matches!(err, super::BbsError::InvalidUrl(_)));
+
+    let requests = server.await.unwrap();
+    assert!(requests[0].starts_with("GET /bbs/rawmode.cgi/radio/22607/1484488601/5- "));
+    assert!(requests[1].starts_with("GET /bbs/rawmode.cgi/radio/22607/1484488601/7- "));
+}
+
+#[tokio::test]
+async fn test_read_compatible_differentially() {
+    fn sjis(text: &str) -> Vec<u8> {
+        encoding_rs::SHIFT_JIS.encode(text).0.into_owned()
     }
+    let line1 = "名無し<>sage<>2025/06/08(日) 14:10:08.12 ID:a<> 1 <>スレ\n";
+    let line2 = "名無し<>sage<>2025/06/08(日) 14:10:09.12 ID:b<> 2 <>\n";
+    let line3 = "名無し<>sage<>2025/06/08(日) 14:10:10.12 ID:c<> 3 <>\n";
+    let lm = ("Last-Modified", "Sun, 08 Jun 2025 05:10:09 GMT");
+    let full = sjis(&format!("{}{}", line1, line2));
+    let (origin, server) = serve_stub(vec![
+        // 初回は全体
+        stub_response("200 OK", &[lm], &full),
+        // 差分
+        stub_response("206 Partial Content", &[lm], &sjis(&format!("\n{}", line3))),
+        // 更新なし
+        stub_response("304 Not Modified", &[], b""),
+        // 縮んだ (あぼーん) ので全体を取り直す
+        stub_response("416 Range Not Satisfiable", &[], b""),
+        stub_response("200 OK", &[lm], &sjis(line1)),
+        // 先頭バイトが合わない (書き換え) ので全体を取り直す
+        stub_response("206 Partial Content", &[lm], &sjis(line2)),
+        stub_response("200 OK", &[lm], &full),
+        // Range を無視して全体が返ってきた
+        stub_response(
+            "200 OK",
+            &[lm],
+            &sjis(&format!("{}{}{}", line1, line2, line3)),
+        ),
+    ])
+    .await;
+    let url = Url::parse(&format!("{}/test/read.cgi/progre/1749359408/", origin)).unwrap();
+    let reader = super::new(&client(), &url).unwrap();
+    let numbers = async |from| -> Vec<u32> {
+        let responses = reader.read("shift_jis", from).await.unwrap();
+        responses.into_iter().map(|x| x.number).collect()
+    };
+
+    assert_eq!(numbers(1).await, [1, 2]);
+    assert_eq!(numbers(3).await, [3]);
+    assert_eq!(numbers(1).await, [1, 2, 3]);
+    assert_eq!(numbers(1).await, [1]);
+    assert_eq!(numbers(1).await, [1, 2]);
+    assert_eq!(numbers(2).await, [2, 3]);
+
+    let requests = server.await.unwrap();
+    assert!(requests[0].starts_with("GET /progre/dat/1749359408.dat "));
+    assert!(!requests[0].to_ascii_lowercase().contains("range:"));
+    let range = format!("range: bytes={}-", full.len() - 1);
+    assert!(requests[1].to_ascii_lowercase().contains(&range));
+    assert!(requests[1].contains("Sun, 08 Jun 2025 05:10:09 GMT"));
+    assert!(!requests[4].to_ascii_lowercase().contains("range:"));
+    assert!(!requests[6].to_ascii_lowercase().contains("range:"));
+}
+
+#[test]
+fn test_read_thread_list() {
+    use super::{compatible, shitaraba};
+
+    let summary = |key, title: &str, res_count| ThreadSummary {
+        key,
+        title: title.to_owned(),
+        res_count,
+    };
+
+    let subject_txt = "1749359408.dat<>連絡スレ Part2 &amp; 雑談 (12)\n\
+                       1749000000.dat<>連絡スレ (1) (1000)\n\
+                       broken\n";
+    assert_eq!(
+        compatible::read_thread_list(subject_txt),
+        [
+            summary(1749359408, "連絡スレ Part2 & 雑談", 12),
+            summary(1749000000, "連絡スレ (1)", 1000),
+        ]
+    );
+
+    let subject_txt = "1484488601.cgi,連絡スレ&lt;2&gt;(5)\n\
+                       1400000000.cgi,連絡スレ(1000)\n\
+                       1484488601.cgi,連絡スレ&lt;2&gt;(5)\n";
+    assert_eq!(
+        shitaraba::read_thread_list(subject_txt),
+        [
+            summary(1484488601, "連絡スレ<2>", 5),
+            summary(1400000000, "連絡スレ", 1000),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_setting_txt() {
+    use super::{ValidationError, board_settings::parse_setting_txt};
+
+    let settings = parse_setting_txt(include_str!("fixtures/SETTING.TXT"));
+    assert_eq!(
+        settings,
+        BoardSettings {
+            title: Some("ぷろぐれ配信連絡板".to_owned()),
+            comment: None,
+            noname_name: Some("名無しさん".to_owned()),
+            max_message_bytes: Some(20),
+            max_lines: Some(4),
+            max_name_bytes: Some(32),
+            max_mail_bytes: Some(16),
+            max_subject_bytes: Some(64),
+            thread_stop: Some(1000),
+        }
+    );
+
+    let sjis = encoding_rs::SHIFT_JIS;
+    assert_eq!(
+        settings.validate(sjis, "", "sage", "あいうえおかきくけこ"),
+        Ok(())
+    );
+    assert_eq!(
+        settings.validate(sjis, "", "sage", "あいうえおかきくけこさ"),
+        Err(ValidationError::MessageTooLong { bytes: 22, max: 20 })
+    );
+    assert_eq!(
+        settings.validate(sjis, "", "sage", "a\nb\nc\nd\ne"),
+        Err(ValidationError::TooManyLines { lines: 5, max: 4 })
+    );
+    assert_eq!(
+        settings.validate(sjis, "", "sagesagesagesagesage", "a"),
+        Err(ValidationError::MailTooLong { bytes: 20, max: 16 })
+    );
+    assert_eq!(
+        settings.validate(encoding_rs::UTF_8, "", "", "あいうえおかき"),
+        Err(ValidationError::MessageTooLong { bytes: 21, max: 20 })
+    );
+}
+
+#[tokio::test]
+async fn test_post_validates_before_sending() {
+    use super::{BbsError, ValidationError, new};
+
+    let setting_txt = encoding_rs::SHIFT_JIS
+        .encode(include_str!("fixtures/SETTING.TXT"))
+        .0
+        .into_owned();
+    let (origin, server) = serve_stub(vec![stub_response("200 OK", &[], &setting_txt)]).await;
+    let url = Url::parse(&format!("{}/test/read.cgi/progre/1749359408/", origin)).unwrap();
+    let thread = new(&client(), &url).unwrap();
+
+    let err = thread
+        .post("shift_jis", "", "sage", "a\nb\nc\nd\ne")
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        BbsError::Validation(ValidationError::TooManyLines { lines: 5, max: 4 })
+    ));
+
+    let requests = server.await.unwrap();
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].starts_with("GET /progre/SETTING.TXT "));
+}
+
+#[tokio::test]
+async fn test_post_confirmation() {
+    use super::new;
+
+    fn sjis(text: &str) -> Vec<u8> {
+        encoding_rs::SHIFT_JIS.encode(text).0.into_owned()
+    }
+    let setting_txt = sjis(include_str!("fixtures/SETTING.TXT"));
+    let confirmation = sjis(include_str!("fixtures/compatible_confirmation.html"));
+    let accepted = sjis(include_str!("fixtures/compatible_accepted.html"));
+    let (origin, server) = serve_stub(vec![
+        stub_response("200 OK", &[], &setting_txt),
+        stub_response(
+            "200 OK",
+            &[("Set-Cookie", "yuki=akari; path=/")],
+            &confirmation,
+        ),
+        stub_response("200 OK", &[], &accepted),
+    ])
+    .await;
+    let url = Url::parse(&format!("{}/test/read.cgi/progre/1749359408/", origin)).unwrap();
+    let thread = new(&client(), &url).unwrap();
+
+    let outcome = thread.post("shift_jis", "", "sage", "test").await.unwrap();
+    assert_eq!(outcome, PostOutcome::Accepted);
+
+    // 確認ページの後に一度だけ送り直し、hidden と発行された Cookie を付ける
+    let requests = server.await.unwrap();
+    assert_eq!(requests.len(), 3);
+    assert!(requests[1].starts_with("POST /test/bbs.cgi "));
+    assert!(!requests[1].contains("feature="));
+    assert!(!requests[1].contains("yuki=akari"));
+    assert!(requests[2].starts_with("POST /test/bbs.cgi "));
+    assert!(requests[2].contains("feature=confirmed%3Aa1b2c3d4"));
+    assert!(requests[2].contains("time=1749359408"));
+    assert!(requests[2].contains("yuki=akari"));
+    // 利用者の本文は確認ページの値で置き換えない
+    assert!(requests[2].contains("MESSAGE=test"));
+}
+
+#[test]
+fn test_parse_setting_cgi() {
+    use super::shitaraba::parse_setting_cgi;
+
+    assert_eq!(
+        parse_setting_cgi(include_str!("fixtures/shitaraba_setting.txt")),
+        BoardSettings {
+            title: Some("ぷろぐれ配信".to_owned()),
+            comment: Some("配信の連絡用です".to_owned()),
+            noname_name: Some("名無しさん".to_owned()),
+            thread_stop: Some(1000),
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_choose_next_thread() {
+    use super::next_thread::choose_next_thread;
+
+    let summary = |key, title: &str, res_count| ThreadSummary {
+        key,
+        title: title.to_owned(),
+        res_count,
+    };
+    let res = |body: &str| Res {
+        number: 1000,
+        name: String::new(),
+        mail: String::new(),
+        date: String::new(),
+        id: None,
+        body: body.to_owned(),
+    };
+    let current = summary(1700000000, "ぷろぐれ配信連絡スレ Part3", 1000);
+    let threads = [
+        summary(1700300000, "雑談スレ", 3),
+        summary(1700200000, "ぷろぐれ配信連絡スレ Part4", 12),
+        summary(1700100000, "【重複】ぷろぐれ配信連絡スレ Part4", 1),
+        summary(1700000000, "ぷろぐれ配信連絡スレ Part3", 1000),
+        summary(1600000000, "ぷろぐれ配信連絡スレ Part2", 1000),
+    ];
+
+    // タイトルが一番似ているもの
+    let next = choose_next_thread(&current, &threads, &[], 1000).unwrap();
+    assert_eq!(next.key, 1700200000);
+
+    // 次スレのリンクがあればそちらを優先する
+    let last = [res(
+        "次スレ https://bbs.jpnkn.com/test/read.cgi/progre/1700100000/",
+    )];
+    let next = choose_next_thread(&current, &threads, &last, 1000).unwrap();
+    assert_eq!(next.key, 1700100000);
+
+    // 古いスレッドや埋まったスレッドへのリンクは使わない
+    let last = [res(
+        "前スレ https://bbs.jpnkn.com/test/read.cgi/progre/1600000000/",
+    )];
+    let next = choose_next_thread(&current, &threads, &last, 1000).unwrap();
+    assert_eq!(next.key, 1700200000);
+
+    let threads = [summary(1700300000, "雑談スレ", 3), current.clone()];
+    assert!(choose_next_thread(&current, &threads, &[], 1000).is_none());
+}
+
+#[tokio::test]
+async fn test_post_following_next_thread() {
+    use super::{NextThread, RetryPolicy, new, post_following_next_thread};
+
+    fn sjis(text: &str) -> Vec<u8> {
+        encoding_rs::SHIFT_JIS.encode(text).0.into_owned()
+    }
+    let stopped = sjis(include_str!("fixtures/compatible_thread_stopped.html"));
+    let accepted = sjis(include_str!("fixtures/compatible_accepted.html"));
+    let subject_txt =
+        sjis("1700200000.dat<>連絡スレ Part4 (12)\n1700000000.dat<>連絡スレ Part3 (1000)\n");
+    let setting_txt = sjis(include_str!("fixtures/SETTING.TXT"));
+    let (origin, server) = serve_stub(vec![
+        stub_response("200 OK", &[], &setting_txt),
+        stub_response("200 OK", &[], &stopped),
+        stub_response("200 OK", &[], &subject_txt),
+        stub_response("404 Not Found", &[], b""),
+        stub_response("200 OK", &[], &accepted),
+    ])
+    .await;
+    let url = Url::parse(&format!("{}/test/read.cgi/progre/1700000000/", origin)).unwrap();
+    let thread = new(&client(), &url).unwrap();
+
+    let (outcome, next) = post_following_next_thread(
+        thread.as_ref(),
+        "shift_jis",
+        "",
+        "",
+        "test",
+        &RetryPolicy {
+            max_retries: 0,
+            ..Default::default()
+        },
+        &|_| {},
+    )
+    .await
+    .unwrap();
+    assert_eq!(outcome, PostOutcome::Accepted);
+    assert_eq!(
+        next,
+        Some(NextThread {
+            key: 1700200000,
+            url: Url::parse(&format!("{}/test/read.cgi/progre/1700200000/", origin)).unwrap(),
+            title: "連絡スレ Part4".to_owned(),
+        })
+    );
+
+    let requests = server.await.unwrap();
+    assert!(requests[0].starts_with("GET /progre/SETTING.TXT "));
+    assert!(requests[1].starts_with("POST /test/bbs.cgi "));
+    assert!(requests[2].starts_with("GET /progre/subject.txt "));
+    assert!(requests[3].starts_with("GET /progre/dat/1700000000.dat "));
+    assert!(requests[4].starts_with("POST /test/bbs.cgi "));
+}
+
+#[tokio::test]
+async fn test_create_thread() {
+    fn sjis(text: &str) -> Vec<u8> {
+        encoding_rs::SHIFT_JIS.encode(text).0.into_owned()
+    }
+    let accepted = sjis(include_str!("fixtures/compatible_accepted.html"));
+    let subject_txt = sjis("1700300000.dat<>新しいスレ (1)\n1700200000.dat<>連絡スレ Part4 (12)\n");
+    let setting_txt = sjis(include_str!("fixtures/SETTING.TXT"));
+    let (origin, server) = serve_stub(vec![
+        stub_response("200 OK", &[], &setting_txt),
+        stub_response("200 OK", &[], &accepted),
+        stub_response("200 OK", &[], &subject_txt),
+    ])
+    .await;
+    let url = Url::parse(&format!("{}/newthread/", origin)).unwrap();
+    let board = super::parse_bbs_url(&client(), url).unwrap().into_board();
+
+    let thread_url = board
+        .create_thread("shift_jis", "新しいスレ", "", "", "test")
+        .await
+        .unwrap();
+    assert_eq!(
+        thread_url,
+        Url::parse(&format!("{}/test/read.cgi/newthread/1700300000/", origin)).unwrap()
+    );
+
+    let requests = server.await.unwrap();
+    assert!(requests[0].starts_with("GET /newthread/SETTING.TXT "));
+    assert!(requests[1].starts_with("POST /test/bbs.cgi "));
+    assert!(requests[2].starts_with("GET /newthread/subject.txt "));
+}
+
+#[tokio::test]
+async fn test_create_shitaraba_thread() {
+    fn euc(text: &str) -> Vec<u8> {
+        encoding_rs::EUC_JP.encode(text).0.into_owned()
+    }
+    let setting_cgi = euc(include_str!("fixtures/shitaraba_setting.txt"));
+    let accepted = euc(include_str!("fixtures/shitaraba_accepted.html"));
+    // 同じタイトルが二つあれば新しい方。掲示板が実体参照にしたものも同じタイトルとみなす
+    let subject_txt = euc(concat!(
+        "1700300000.cgi,新しい&lt;スレ&gt;  (1)\n",
+        "1700200000.cgi,新しい<スレ> (12)\n",
+        "1700100000.cgi,連絡スレ (30)\n",
+        "1700300000.cgi,新しい&lt;スレ&gt;  (1)\n",
+    ));
+    let (origin, server) = serve_stub(vec![
+        stub_response("200 OK", &[], &setting_cgi),
+        stub_response("200 OK", &[], &accepted),
+        stub_response("200 OK", &[], &subject_txt),
+    ])
+    .await;
+    let url = Url::parse(&format!("{}/bbs/read.cgi/radio/22607/1484488601/", origin)).unwrap();
+    let board = super::parse_bbs_url(&client(), url).unwrap().into_board();
+
+    let thread_url = board
+        .create_thread("euc-jp", " 新しい<スレ> ", "", "", "test")
+        .await
+        .unwrap();
+    assert_eq!(
+        thread_url,
+        Url::parse(&format!("{}/bbs/read.cgi/radio/22607/1700300000/", origin)).unwrap()
+    );
+
+    let requests = server.await.unwrap();
+    assert!(requests[0].starts_with("GET /bbs/api/setting.cgi/radio/22607/ "));
+    assert!(requests[1].starts_with("POST /bbs/write.cgi/radio/22607/new/ "));
+    assert!(requests[1].contains("SUBJECT="));
+    assert!(requests[2].starts_with("GET /radio/22607/subject.txt "));
+}
+
+#[test]
+fn test_parse_offlaw() {
+    use super::{machi::parse_offlaw, subject::read_cgi_thread_list};
+
+    let responses = parse_offlaw(include_str!("fixtures/machi_offlaw.txt"));
+    assert_eq!(
+        responses,
+        [
+            Res {
+                number: 1,
+                name: "名無しさん".to_owned(),
+                mail: "".to_owned(),
+                date: "2025/06/08(日) 14:10:08".to_owned(),
+                id: Some("AbCdEfGh".to_owned()),
+                body: "地元の話題\nなんでもどうぞ".to_owned(),
+            },
+            Res {
+                number: 2,
+                name: "ぷろぐれ".to_owned(),
+                mail: "sage".to_owned(),
+                date: "2025/06/08(日) 14:11:00".to_owned(),
+                id: Some("IjKlMnOp".to_owned()),
+                body: ">>1 乙".to_owned(),
+            },
+        ]
+    );
+
+    let threads =
+        read_cgi_thread_list("1700000000.cgi,地元スレ Part2(2)\n1690000000.cgi,雑談(1000)\n");
+    assert_eq!(
+        threads,
+        [
+            ThreadSummary {
+                key: 1700000000,
+                title: "地元スレ Part2".to_owned(),
+                res_count: 2,
+            },
+            ThreadSummary {
+                key: 1690000000,
+                title: "雑談".to_owned(),
+                res_count: 1000,
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_bbs_error() {
+    use reqwest::StatusCode;
+
+    use super::{BbsError, new};
+
+    let (origin, server) = serve_stub(vec![stub_response("404 Not Found", &[], b"")]).await;
+    let url = Url::parse(&format!("{}/test/read.cgi/progre/1749359408/", origin)).unwrap();
+    let reader = new(&client(), &url).unwrap();
+
+    // 知らない文字コードでは通信する前に失敗する
+    let err = reader.read("no-such-charset", 1).await.unwrap_err();
+    assert!(matches!(err, BbsError::UnsupportedCharset(x) if x == "no-such-charset"));
+
+    let err = reader.read("shift_jis", 1).await.unwrap_err();
+    assert!(matches!(
+        err,
+        BbsError::HttpStatus {
+            status: StatusCode::NOT_FOUND,
+            ..
+        }
+    ));
+    let requests = server.await.unwrap();
+    assert_eq!(requests.len(), 1);
+
+    let err = new(&client(), &Url::parse("https://example.com/").unwrap())
+        .err()
+        .unwrap();
+    assert!(matches!(err, BbsError::InvalidUrl(_)));
+
+    assert!(PostOutcome::Accepted.into_result().is_ok());
+    assert!(matches!(
+        PostOutcome::ThreadStopped.into_result(),
+        Err(BbsError::ThreadStopped)
+    ));
+    assert!(matches!(
+        PostOutcome::Rejected { reason: "本文がありません".to_owned() }.into_result(),
+        Err(BbsError::Rejected(x)) if x == "本文がありません"
+    ));
+}
+
+#[test]
+fn test_unmappable_policy() {
+    use std::collections::BTreeMap;
+
+    use encoding_rs::{EUC_JP, SHIFT_JIS};
+
+    use super::{
+        BbsError, Unmappable, UnmappablePolicy,
+        encoding::{charset_percent_encode, find_unmappable},
+    };
+
+    fn unmappable(text: &str, replacement: Option<&str>) -> Unmappable {
+        Unmappable {
+            text: text.to_owned(),
+            replacement: replacement.map(str::to_owned),
+        }
+    }
+    // 絵文字、ZWJ でつないだ絵文字、肌の色つき絵文字、JIS X 0213 にしかない文字
+    let text = "了解😂 👨‍👩‍👧 👍🏻 ㇰ𠀋";
+
+    for encoding in [SHIFT_JIS, EUC_JP] {
+        assert_eq!(
+            find_unmappable(encoding, text, &UnmappablePolicy::NumericReference),
+            [
+                unmappable("😂", Some("&#128514;")),
+                unmappable("👨‍👩‍👧", Some("&#128104;&#8205;&#128105;&#8205;&#128103;")),
+                unmappable("👍🏻", Some("&#128077;&#127995;")),
+                unmappable("ㇰ", Some("&#12784;")),
+                unmappable("𠀋", Some("&#131083;")),
+            ]
+        );
+    }
+
+    let table = BTreeMap::from([
+        ("😂".to_owned(), "(笑)".to_owned()),
+        ("👨‍👩‍👧".to_owned(), "(家族)".to_owned()),
+        ("👨".to_owned(), "(男)".to_owned()),
+    ]);
+    let policy = UnmappablePolicy::Substitute(table);
+    assert_eq!(
+        find_unmappable(SHIFT_JIS, text, &policy),
+        [
+            unmappable("😂", Some("(笑)")),
+            unmappable("👨‍👩‍👧", Some("(家族)")),
+            unmappable("👍🏻", Some("&#128077;&#127995;")),
+            unmappable("ㇰ", Some("&#12784;")),
+            unmappable("𠀋", Some("&#131083;")),
+        ]
+    );
+    let encoded = charset_percent_encode(SHIFT_JIS, "了解😂", &policy).unwrap();
+    let expected = charset_percent_encode(SHIFT_JIS, "了解(笑)", &policy).unwrap();
+    assert_eq!(encoded, expected);
+
+    let err = charset_percent_encode(SHIFT_JIS, text, &UnmappablePolicy::Refuse).unwrap_err();
+    assert!(matches!(
+        err,
+        BbsError::UnmappableCharacters(x) if x == ["😂", "👨‍👩‍👧", "👍🏻", "ㇰ", "𠀋"]
+    ));
+    // 表せる文字だけなら拒否しない
+    assert!(charset_percent_encode(SHIFT_JIS, "了解", &UnmappablePolicy::Refuse).is_ok());
+}
+
+#[tokio::test]
+async fn test_decode_responses_by_charset() {
+    use super::{BbsUrl, fetch_thread_url_encoding_name, new};
+
+    let page = "<html><head><meta charset=\"UTF-8\"><title>連絡スレ</title></head></html>";
+    let rejected = "<html><head><title>ＥＲＲＯＲ！</title></head>\
+        <body>ＥＲＲＯＲ：本文がありません！</body></html>";
+    let (origin, server) = serve_stub(vec![
+        // Content-Type が無くてもページの meta から UTF-8 と分かる
+        stub_response("200 OK", &[], page.as_bytes()),
+        stub_response("200 OK", &[], "1700000000.dat<>連絡スレ (12)\n".as_bytes()),
+        // Content-Type があればそれを優先する
+        stub_response(
+            "200 OK",
+            &[("Content-Type", "text/plain; charset=Shift_JIS")],
+            &encoding_rs::SHIFT_JIS
+                .encode("1700000000.dat<>連絡スレ (13)\n")
+                .0,
+        ),
+        stub_response("404 Not Found", &[], b""),
+        stub_response(
+            "200 OK",
+            &[("Content-Type", "text/html; charset=utf-8")],
+            rejected.as_bytes(),
+        ),
+    ])
+    .await;
+    let url = Url::parse(&format!("{}/test/read.cgi/progre/1700000000/", origin)).unwrap();
+    let bbs_url = super::parse_bbs_url(&client(), url.clone()).unwrap();
+    let (_, charset, title) = fetch_thread_url_encoding_name(&client(), &bbs_url)
+        .await
+        .unwrap();
+    assert_eq!(charset, "UTF-8");
+    assert_eq!(title, "連絡スレ");
+    let BbsUrl::Thread(_, thread) = bbs_url else {
+        unreachable!()
+    };
+    let board = thread.board();
+    assert_eq!(
+        board.fetch_thread_list().await.unwrap()[0].title,
+        "連絡スレ"
+    );
+    assert_eq!(board.fetch_thread_list().await.unwrap()[0].res_count, 13);
+
+    let thread = new(&client(), &url).unwrap();
+    let outcome = thread.post("shift_jis", "", "", "").await.unwrap();
+    assert_eq!(
+        outcome,
+        PostOutcome::Rejected {
+            reason: "本文がありません！".to_owned()
+        }
+    );
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_bbs_client() {
+    use std::time::Duration;
+
+    use super::{BbsClient, BbsClientConfig, BbsError};
+
+    let (origin, server) = serve_stub(vec![
+        stub_response("302 Found", &[("Location", "/elsewhere/")], b""),
+        stub_response("200 OK", &[], b""),
+    ])
+    .await;
+    let config = BbsClientConfig {
+        user_agent: "test-agent/1.0".to_owned(),
+        connect_timeout: Duration::from_secs(1),
+        read_timeout: Duration::from_secs(1),
+        max_redirects: 0,
+        proxy: None,
+    };
+    let client = BbsClient::new(&config).unwrap();
+    let url = Url::parse(&format!("{}/progre/", origin)).unwrap();
+    let board = super::parse_bbs_url(&client, url).unwrap().into_board();
+
+    // リダイレクトを追わないので 302 のままエラーになる
+    let err = board.fetch_thread_list().await.unwrap_err();
+    assert!(matches!(
+        err,
+        BbsError::HttpStatus { status, .. } if status == reqwest::StatusCode::FOUND
+    ));
+    let board_settings = board.board_settings().await.unwrap();
+    assert_eq!(board_settings, BoardSettings::default());
+
+    let requests = server.await.unwrap();
+    assert_eq!(requests.len(), 2);
+    assert!(requests.iter().all(|x| {
+        x.to_ascii_lowercase()
+            .contains("user-agent: test-agent/1.0")
+    }));
+
+    let config = BbsClientConfig {
+        proxy: Some("not a proxy".to_owned()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        BbsClient::new(&config),
+        Err(BbsError::InvalidProxy(_))
+    ));
+}
+
+#[test]
+fn test_is_safe_to_resend() {
+    use reqwest::StatusCode;
+
+    use super::{BbsError, is_safe_to_resend};
+
+    let status = |status, empty_body| BbsError::HttpStatus {
+        status,
+        url: String::new(),
+        empty_body,
+    };
+    assert!(is_safe_to_resend(&BbsError::RateLimited {
+        retry_after: None
+    }));
+    assert!(is_safe_to_resend(&status(StatusCode::BAD_GATEWAY, true)));
+    // 掲示板が本文を返したものや 500 は届いているかもしれない
+    assert!(!is_safe_to_resend(&status(StatusCode::BAD_GATEWAY, false)));
+    assert!(!is_safe_to_resend(&status(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        true
+    )));
+    assert!(!is_safe_to_resend(&BbsError::Rejected("ERROR".to_owned())));
+    assert!(is_safe_to_resend(&BbsError::PartiallyPosted {
+        posted: 1,
+        total: 2,
+        source: Box::new(status(StatusCode::SERVICE_UNAVAILABLE, true)),
+    }));
+}
+
+#[tokio::test]
+async fn test_post_with_retry() {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use reqwest::StatusCode;
+
+    use super::{
+        RetryPolicy, RetryProgress, new,
+        retry::{RetryReason, post_with_retry},
+    };
+
+    fn sjis(text: &str) -> Vec<u8> {
+        encoding_rs::SHIFT_JIS.encode(text).0.into_owned()
+    }
+    let rate_limited = |wait: &str| {
+        sjis(&format!(
+            "<html><head><title>ＥＲＲＯＲ！</title></head>\
+            <body>ＥＲＲＯＲ：連続投稿ですか？？ {}たたないと書けません。</body></html>",
+            wait
+        ))
+    };
+    let accepted = sjis(include_str!("fixtures/compatible_accepted.html"));
+    let setting_txt = sjis(include_str!("fixtures/SETTING.TXT"));
+    let (origin, server) = serve_stub(vec![
+        stub_response("200 OK", &[], &setting_txt),
+        stub_response("200 OK", &[], &rate_limited("1秒")),
+        stub_response("503 Service Unavailable", &[], b""),
+        stub_response("200 OK", &[], &accepted),
+        // 待ち時間が予算を超えるなら諦める
+        stub_response("200 OK", &[], &rate_limited("10分")),
+        // 書き込みが届いたかもしれないものは書き込み直さない
+        stub_response("500 Internal Server Error", &[], b""),
+        stub_response("503 Service Unavailable", &[], b"busy"),
+    ])
+    .await;
+    let url = Url::parse(&format!("{}/test/read.cgi/progre/1749359408/", origin)).unwrap();
+    let thread = new(&client(), &url).unwrap();
+    let policy = RetryPolicy {
+        max_retries: 3,
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(100),
+        budget: Duration::from_secs(60),
+    };
+    let progress = Arc::new(Mutex::new(Vec::new()));
+
+    let on_retry = {
+        let progress = progress.clone();
+        move |x| progress.lock().unwrap().push(x)
+    };
+    let outcome = post_with_retry(
+        thread.as_ref(),
+        "shift_jis",
+        "",
+        "",
+        "test",
+        &policy,
+        &on_retry,
+    )
+    .await
+    .unwrap();
+    assert_eq!(outcome, PostOutcome::Accepted);
+    assert_eq!(
+        *progress.lock().unwrap(),
+        [
+            RetryProgress {
+                attempt: 1,
+                wait: Duration::from_secs(1),
+                reason: RetryReason::RateLimited,
+            },
+            RetryProgress {
+                attempt: 2,
+                wait: Duration::from_millis(20),
+                reason: RetryReason::ServerError(StatusCode::SERVICE_UNAVAILABLE),
+            },
+        ]
+    );
+
+    progress.lock().unwrap().clear();
+    let outcome = post_with_retry(
+        thread.as_ref(),
+        "shift_jis",
+        "",
+        "",
+        "test",
+        &policy,
+        &on_retry,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        outcome,
+        PostOutcome::RateLimited {
+            retry_after: Some(Duration::from_secs(600))
+        }
+    );
+    assert!(progress.lock().unwrap().is_empty());
+
+    for status in [
+        StatusCode::INTERNAL_SERVER_ERROR,
+        StatusCode::SERVICE_UNAVAILABLE,
+    ] {
+        let err = post_with_retry(
+            thread.as_ref(),
+            "shift_jis",
+            "",
+            "",
+            "test",
+            &policy,
+            &on_retry,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, super::BbsError::HttpStatus { status: x, .. } if x == status));
+    }
+    assert!(progress.lock().unwrap().is_empty());
+
+    let requests = server.await.unwrap();
+    assert_eq!(requests.len(), 7);
+}
+
+#[test]
+fn test_split_message() {
+    use super::split_message;
+
+    let encoding = encoding_rs::SHIFT_JIS;
+    let settings = BoardSettings {
+        max_message_bytes: Some(20),
+        max_lines: Some(4),
+        ..Default::default()
+    };
+    assert_eq!(
+        split_message(encoding, &settings, "短い\n本文"),
+        ["短い\n本文"]
+    );
+
+    // 長すぎる行は文字の区切りで分ける
+    let parts = split_message(
+        encoding,
+        &settings,
+        "あいう\nえお\nかきくけこさしすせそ\nabc",
+    );
+    assert_eq!(
+        parts,
+        [
+            "(1/3)\nあいう\nえお",
+            "(2/3)\nかきくけこさし",
+            "(3/3)\nすせそ\nabc"
+        ]
+    );
+    for part in &parts {
+        settings.validate(encoding, "", "", part).unwrap();
+    }
+
+    let settings = BoardSettings {
+        max_lines: Some(4),
+        ..Default::default()
+    };
+    assert_eq!(
+        split_message(encoding, &settings, "1\n2\n3\n4\n5\n6\n7"),
+        ["(1/3)\n1\n2\n3", "(2/3)\n4\n5\n6", "(3/3)\n7"]
+    );
+}
+
+#[tokio::test]
+async fn test_post_parts() {
+    use std::time::Duration;
+
+    use super::{BbsError, RetryPolicy, new, post_parts};
+
+    fn sjis(text: &str) -> Vec<u8> {
+        encoding_rs::SHIFT_JIS.encode(text).0.into_owned()
+    }
+    let accepted = sjis(include_str!("fixtures/compatible_accepted.html"));
+    let error = sjis(include_str!("fixtures/compatible_error.html"));
+    let setting_txt = sjis(include_str!("fixtures/SETTING.TXT"));
+    let (origin, server) = serve_stub(vec![
+        stub_response("200 OK", &[], &setting_txt),
+        stub_response("200 OK", &[], &accepted),
+        stub_response("200 OK", &[], &error),
+    ])
+    .await;
+    let url = Url::parse(&format!("{}/test/read.cgi/progre/1749359408/", origin)).unwrap();
+    let thread = new(&client(), &url).unwrap();
+    let parts = ["(1/3)\na", "(2/3)\nb", "(3/3)\nc"].map(str::to_owned);
+    let posted_counts = std::sync::Mutex::new(Vec::new());
+
+    // 途中で失敗したら、どこまで書き込めたかを返す
+    let err = post_parts(
+        thread.as_ref(),
+        "shift_jis",
+        "",
+        "",
+        &parts,
+        0,
+        Duration::ZERO,
+        &RetryPolicy {
+            max_retries: 0,
+            ..Default::default()
+        },
+        &|_| {},
+        &|posted| posted_counts.lock().unwrap().push(posted),
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        BbsError::PartiallyPosted { posted: 1, total: 3, source }
+            if matches!(*source, BbsError::Rejected(_))
+    ));
+    assert_eq!(*posted_counts.lock().unwrap(), [1]);
+
+    let requests = server.await.unwrap();
+    assert_eq!(requests.len(), 3);
+    assert!(requests[1].starts_with("POST /test/bbs.cgi "));
+    assert!(requests[2].starts_with("POST /test/bbs.cgi "));
+}
+
+#[test]
+fn test_tripcode() {
+    use super::{name_with_trip, trip::tripcode};
+
+    let encoding = encoding_rs::SHIFT_JIS;
+    // 値は perl の crypt と sha1 で確かめたもの
+    let data = [
+        ("istrip", "/WG5qp963c"),
+        ("test", ".CzKQna1OU"),
+        ("あ", "3zNBOPkseQ"),
+        ("ab:<", "5eQJ.WjLLM"),
+        ("123456789012", "jZk8zfYo4m4X"),
+        ("abcdefghijklmnop", "FPOZUois0Ynm"),
+        ("あいうえおかき", "7I7kz5lqVmYX"),
+        ("#0123456789abcdefAZ", ".DKLgAfPs6"),
+        ("#0123456789abcdef", "ClNHFHdYIw"),
+        ("#0123456789abcdeg", "???"),
+        ("$abcdefghijklmnop", "???"),
+    ];
+    for (key, trip) in data {
+        assert_eq!(tripcode(encoding, key), trip, "{}", key);
+    }
+
+    assert_eq!(name_with_trip(encoding, "名無し"), "名無し");
+    assert_eq!(name_with_trip(encoding, "名前#istrip"), "名前◆/WG5qp963c");
+    assert_eq!(name_with_trip(encoding, "◆#istrip"), "◇◆/WG5qp963c");
+    assert_eq!(
+        name_with_trip(encoding, "##0123456789abcdef"),
+        "◆ClNHFHdYIw"
+    );
 }
@@ -0,0 +1,70 @@
+use std::sync::LazyLock;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use encoding_rs::Encoding;
+use regex::Regex;
+use sha1::{Digest, Sha1};
+
+/// 掲示板が不正なキーに返すトリップ
+const INVALID_TRIP: &str = "???";
+
+/// crypt(3) の結果の末尾 10 文字
+#[allow(deprecated)]
+fn crypt_trip(key: &[u8], salt: &str) -> String {
+    // C の crypt と同じく NUL で打ち切る
+    let key = key.split(|x| *x == 0).next().unwrap_or_default();
+    let hash = pwhash::unix_crypt::hash_with(salt, key).unwrap();
+    hash[hash.len() - 10..].to_owned()
+}
+
+/// キーの 2, 3 バイト目から crypt(3) の salt を作る
+fn classic_salt(key: &[u8]) -> String {
+    let mut bytes = key.to_vec();
+    bytes.extend_from_slice(b"H.");
+    bytes[1..3]
+        .iter()
+        .map(|&b| match b {
+            b':'..=b'@' => (b'A' + (b - b':')) as char,
+            b'['..=b'`' => (b'a' + (b - b'[')) as char,
+            b'.'..=b'z' => b as char,
+            _ => '.',
+        })
+        .collect()
+}
+
+static RAW_KEY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^#([0-9A-Fa-f]{16})([./0-9A-Za-z]{0,2})$").unwrap());
+
+/// `##` に続く 16 桁の 16 進数と 2 文字までの salt をそのまま使う
+fn raw_key_trip(key: &str) -> Option<String> {
+    let c = RAW_KEY.captures(key)?;
+    let bytes: Vec<u8> = (0..16)
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&c[1][i..i + 2], 16).unwrap())
+        .collect();
+    let salt = format!("{}..", &c[2]);
+    Some(crypt_trip(&bytes, &salt[..2]))
+}
+
+/// `#` に続くキーからトリップを作る。キーは板の文字コードで数える
+pub fn tripcode(encoding: &'static Encoding, key: &str) -> String {
+    let bytes = encoding.encode(key).0;
+    if bytes.len() < 12 {
+        return crypt_trip(&bytes, &classic_salt(&bytes));
+    }
+    match bytes[0] {
+        b'#' => raw_key_trip(key).unwrap_or_else(|| INVALID_TRIP.to_owned()),
+        // 将来の拡張のために予約されている
+        b'$' => INVALID_TRIP.to_owned(),
+        _ => STANDARD.encode(Sha1::digest(&bytes))[..12].replace('+', "."),
+    }
+}
+
+/// `名前#キー` を掲示板に表示される `名前◆トリップ` にする。キーが無ければそのまま
+pub fn name_with_trip(encoding: &'static Encoding, name: &str) -> String {
+    let Some((handle, key)) = name.split_once('#') else {
+        return name.to_owned();
+    };
+    // 名前の ◆ は掲示板が ◇ に置き換える
+    format!("{}◆{}", handle.replace('◆', "◇"), tripcode(encoding, key))
+}
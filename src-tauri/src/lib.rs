@@ -1,6 +1,10 @@
+mod app_dir;
 mod bbs;
 mod menu_bar;
+mod outbox;
 mod popover;
+mod profile;
+mod settings;
 mod system_tray;
 
 use menu_bar::create_menu_bar;
@@ -0,0 +1,286 @@
+#[cfg(test)]
+mod test;
+
+use std::{
+    fs,
+    future::Future,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::spawn;
+use tokio::{
+    sync::{Notify, oneshot},
+    time::sleep,
+};
+use tracing::{info, warn};
+
+use crate::{
+    app_dir::write_json_atomically,
+    bbs::{BbsError, is_safe_to_resend},
+};
+
+/// まだ送れていない書き込み
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub id: u64,
+    pub url: String,
+    pub name: String,
+    pub email: String,
+    pub comment: String,
+    /// 板の上限に合わせて分けて書き込む
+    #[serde(default)]
+    pub split: bool,
+    /// 分けた本文。分ける前は空
+    #[serde(default)]
+    pub parts: Vec<String>,
+    /// 分けて書き込んだうち、書き込み済みの件数
+    #[serde(default)]
+    pub posted_parts: usize,
+    pub attempts: u32,
+    /// 最後に送れなかった理由
+    pub last_error: Option<String>,
+    /// 送り直しても通らない失敗で、利用者が再送か破棄を選ぶまで止めている
+    pub held: bool,
+}
+
+/// 送信のタスクからも呼ぶので、取り出してロックを外してから呼ぶ
+type OutboxObserver = Arc<dyn Fn(Vec<OutboxEntry>) + Send + Sync + 'static>;
+
+/// ファイルに書き出す内容
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct OutboxState {
+    /// 次に振る id。捨てたものの id を使い回さないように残しておく
+    next_id: u64,
+    entries: Vec<OutboxEntry>,
+}
+
+/// 書き込みをファイルに残し、送れるまで順番に送り直す
+pub struct Outbox {
+    path: Option<PathBuf>,
+    state: Mutex<OutboxState>,
+    /// 送信中の書き込みの id と、送信を止めるための送り手
+    sending: Mutex<Option<(u64, oneshot::Sender<()>)>>,
+    observer: Mutex<Option<OutboxObserver>>,
+    changed: Notify,
+}
+
+/// `spawn_worker` の `send` が送れなかったときに返す
+#[derive(Debug)]
+pub enum SendError {
+    /// 送信待ちに残す。`is_safe_to_resend` なら待って送り直し、そうでなければ止めて利用者に任せる
+    Keep(BbsError),
+    /// 送信待ちから外す
+    Discard(BbsError),
+}
+
+impl From<BbsError> for SendError {
+    fn from(err: BbsError) -> Self {
+        SendError::Keep(err)
+    }
+}
+
+/// `attempts` 回送れなかった後に待つ時間。`retry_interval` から倍々に延ばす
+pub fn retry_wait(retry_interval: Duration, attempts: u32) -> Duration {
+    retry_interval.saturating_mul(2u32.pow(attempts.min(6)))
+}
+
+impl Outbox {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let mut state: OutboxState = path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| {
+                serde_json::from_slice(&bytes)
+                    .inspect_err(|err| warn!("broken outbox: {:?}", err))
+                    .ok()
+            })
+            .unwrap_or_default();
+        let max_id = state.entries.iter().map(|x| x.id).max().unwrap_or(0);
+        state.next_id = state.next_id.max(max_id + 1);
+        Self {
+            path,
+            state: Mutex::new(state),
+            sending: Mutex::new(None),
+            observer: Mutex::new(None),
+            changed: Notify::new(),
+        }
+    }
+
+    pub fn entries(&self) -> Vec<OutboxEntry> {
+        self.state.lock().unwrap().entries.clone()
+    }
+
+    pub fn push(
+        &self,
+        url: String,
+        name: String,
+        email: String,
+        comment: String,
+        split: bool,
+    ) -> u64 {
+        let mut id = 0;
+        self.update(true, |state| {
+            id = state.next_id;
+            state.next_id += 1;
+            state.entries.push(OutboxEntry {
+                id,
+                url,
+                name,
+                email,
+                comment,
+                split,
+                parts: Vec::new(),
+                posted_parts: 0,
+                attempts: 0,
+                last_error: None,
+                held: false,
+            });
+        });
+        id
+    }
+
+    /// 送信中なら送信を止める。止める前に掲示板に届いていることはある
+    pub fn discard(&self, id: u64) {
+        // ワーカーが先頭を取り出して送信中にするまでと同じロックの中で見る
+        self.update(true, |state| {
+            let mut sending = self.sending.lock().unwrap();
+            if sending.as_ref().is_some_and(|(x, _)| *x == id) {
+                let (_, cancel) = sending.take().unwrap();
+                let _ = cancel.send(());
+            }
+            state.entries.retain(|x| x.id != id);
+        });
+    }
+
+    /// 分けた本文を残し、送り直すときに同じように分ける
+    pub fn set_parts(&self, id: u64, parts: Vec<String>) {
+        self.update(false, |state| {
+            if let Some(entry) = state.entries.iter_mut().find(|x| x.id == id) {
+                entry.parts = parts;
+            }
+        });
+    }
+
+    /// 分けて書き込むたびに呼び、落ちても書き込み済みのものを送り直さないようにする
+    pub fn set_posted_parts(&self, id: u64, posted: usize) {
+        self.update(false, |state| {
+            if let Some(entry) = state.entries.iter_mut().find(|x| x.id == id) {
+                entry.posted_parts = posted;
+            }
+        });
+    }
+
+    /// 止めている書き込みを送り直す
+    pub fn retry(&self, id: u64) {
+        self.update(true, |state| {
+            if let Some(entry) = state.entries.iter_mut().find(|x| x.id == id) {
+                entry.held = false;
+            }
+        });
+    }
+
+    pub fn subscribe<F>(&self, observer: F)
+    where
+        F: Fn(Vec<OutboxEntry>) + Send + Sync + 'static,
+    {
+        *self.observer.lock().unwrap() = Some(Arc::new(observer));
+    }
+
+    /// 変更してファイルに書き出す。`wake` なら待っている送信を起こす
+    fn update(&self, wake: bool, f: impl FnOnce(&mut OutboxState)) {
+        let entries = {
+            let mut state = self.state.lock().unwrap();
+            f(&mut state);
+            self.save(&state);
+            state.entries.clone()
+        };
+        let observer = self.observer.lock().unwrap().clone();
+        if let Some(observer) = observer {
+            observer(entries);
+        }
+        if wake {
+            self.changed.notify_one();
+        }
+    }
+
+    fn save(&self, state: &OutboxState) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Err(err) = write_json_atomically(path, state) {
+            warn!("failed to save outbox: {:?}", err);
+        }
+    }
+
+    /// 先頭から順に `send` で送る。失敗したら `retry_interval` から倍々に待って送り直す。
+    /// 送信中に破棄されたら `send` を止める
+    pub fn spawn_worker<F, Fut>(self: &Arc<Self>, retry_interval: Duration, send: F)
+    where
+        F: Fn(OutboxEntry) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), SendError>> + Send,
+    {
+        let this = self.clone();
+        spawn(async move {
+            loop {
+                let (cancel, cancelled) = oneshot::channel();
+                let head = {
+                    // 破棄と入れ違わないよう、取り出すのと送信中にするのを同じロックの中で行う
+                    let state = this.state.lock().unwrap();
+                    let head = state.entries.first().filter(|x| !x.held).cloned();
+                    if let Some(entry) = &head {
+                        *this.sending.lock().unwrap() = Some((entry.id, cancel));
+                    }
+                    head
+                };
+                let Some(entry) = head else {
+                    this.changed.notified().await;
+                    continue;
+                };
+                let result = tokio::select! {
+                    result = send(entry.clone()) => Some(result),
+                    _ = cancelled => None,
+                };
+                *this.sending.lock().unwrap() = None;
+                let err = match result {
+                    Some(Ok(())) => {
+                        info!("sent outbox entry: {}", entry.id);
+                        this.update(false, |state| state.entries.retain(|x| x.id != entry.id));
+                        continue;
+                    }
+                    Some(Err(SendError::Keep(err))) => err,
+                    Some(Err(SendError::Discard(err))) => {
+                        info!("discarded outbox entry: {}: {}", entry.id, err);
+                        this.update(false, |state| state.entries.retain(|x| x.id != entry.id));
+                        continue;
+                    }
+                    None => {
+                        info!("cancelled outbox entry: {}", entry.id);
+                        continue;
+                    }
+                };
+                let retryable = is_safe_to_resend(&err);
+                this.update(false, |state| {
+                    if let Some(x) = state.entries.iter_mut().find(|x| x.id == entry.id) {
+                        if let BbsError::PartiallyPosted { posted, .. } = &err {
+                            x.posted_parts = *posted;
+                        }
+                        x.attempts += 1;
+                        x.last_error = Some(err.to_string());
+                        x.held = !retryable;
+                    }
+                });
+                if retryable {
+                    let wait = retry_wait(retry_interval, entry.attempts);
+                    // 新しい書き込みや破棄があれば待たずに見直す
+                    tokio::select! {
+                        _ = sleep(wait) => {}
+                        _ = this.changed.notified() => {}
+                    }
+                }
+            }
+        });
+    }
+}
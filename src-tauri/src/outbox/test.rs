@@ -0,0 +1,141 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::time::sleep;
+
+use crate::bbs::BbsError;
+
+use super::{Outbox, SendError};
+
+/// 条件を満たすまで少しずつ待つ
+async fn wait_until(f: impl Fn() -> bool) {
+    for _ in 0..200 {
+        if f() {
+            return;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    panic!("timed out");
+}
+
+fn push(outbox: &Outbox, comment: &str) -> u64 {
+    outbox.push(
+        "https://example.com/test/read.cgi/board/1/".to_owned(),
+        String::new(),
+        "sage".to_owned(),
+        comment.to_owned(),
+        false,
+    )
+}
+
+#[test]
+fn test_outbox_persistence() {
+    let path = std::env::temp_dir().join(format!("outbox-test-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let outbox = Outbox::load(Some(path.clone()));
+    let first = push(&outbox, "1");
+    let second = push(&outbox, "2");
+    assert_ne!(first, second);
+    outbox.discard(first);
+    // 分けた本文と書き込み済みの件数も残す
+    outbox.set_parts(second, vec!["(1/2)".to_owned(), "(2/2)".to_owned()]);
+    outbox.set_posted_parts(second, 1);
+
+    let reloaded = Outbox::load(Some(path.clone()));
+    assert_eq!(reloaded.entries(), outbox.entries());
+    assert_eq!(reloaded.entries()[0].comment, "2");
+    assert_eq!(reloaded.entries()[0].parts.len(), 2);
+    assert_eq!(reloaded.entries()[0].posted_parts, 1);
+
+    // 捨てた id は読み直しても使い回さない
+    reloaded.discard(second);
+    let third = push(&reloaded, "3");
+    assert!(third > second);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_outbox_worker() {
+    // 1 回目はネットワークエラー、"refused" は送り直しても通らない
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let failed_once = Arc::new(Mutex::new(false));
+    let outbox = Arc::new(Outbox::load(None));
+    push(&outbox, "1");
+    push(&outbox, "2");
+    let refused = push(&outbox, "refused");
+    push(&outbox, "3");
+
+    outbox.spawn_worker(Duration::from_millis(10), {
+        let sent = sent.clone();
+        let failed_once = failed_once.clone();
+        move |entry| {
+            let sent = sent.clone();
+            let failed_once = failed_once.clone();
+            async move {
+                if entry.comment == "refused" {
+                    return Err(BbsError::Rejected("ERROR".to_owned()).into());
+                }
+                if !std::mem::replace(&mut *failed_once.lock().unwrap(), true) {
+                    return Err(BbsError::RateLimited { retry_after: None }.into());
+                }
+                sent.lock().unwrap().push(entry.comment);
+                Ok(())
+            }
+        }
+    });
+
+    // 送れなかったものは順番を変えずに送り直し、通らないものの前で止まる
+    wait_until(|| outbox.entries().first().is_some_and(|x| x.held)).await;
+    assert_eq!(*sent.lock().unwrap(), ["1", "2"]);
+    let entries = outbox.entries();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].id, refused);
+    assert_eq!(entries[0].attempts, 1);
+    assert_eq!(
+        entries[0].last_error.as_deref(),
+        Some("書き込めませんでした: ERROR")
+    );
+
+    outbox.retry(refused);
+    wait_until(|| outbox.entries()[0].attempts == 2).await;
+    assert!(outbox.entries()[0].held);
+
+    outbox.discard(refused);
+    wait_until(|| outbox.entries().is_empty()).await;
+    assert_eq!(*sent.lock().unwrap(), ["1", "2", "3"]);
+}
+
+#[tokio::test]
+async fn test_outbox_discard() {
+    // "hang" は返ってこない。"dropped" は送信待ちから外してもらう
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let outbox = Arc::new(Outbox::load(None));
+    let hang = push(&outbox, "hang");
+    push(&outbox, "1");
+    push(&outbox, "dropped");
+
+    outbox.spawn_worker(Duration::from_millis(10), {
+        let sent = sent.clone();
+        move |entry| {
+            let sent = sent.clone();
+            async move {
+                sent.lock().unwrap().push(entry.comment.clone());
+                match entry.comment.as_str() {
+                    "hang" => std::future::pending().await,
+                    "dropped" => Err(SendError::Discard(BbsError::ThreadStopped)),
+                    _ => Ok(()),
+                }
+            }
+        }
+    });
+
+    // 送信中のものを捨てたら送信を止めて次へ進む
+    wait_until(|| !sent.lock().unwrap().is_empty()).await;
+    outbox.discard(hang);
+    wait_until(|| outbox.entries().is_empty()).await;
+    assert_eq!(*sent.lock().unwrap(), ["hang", "1", "dropped"]);
+}
@@ -1,25 +1,125 @@
 use objc2::{rc::Retained, runtime::ProtocolObject, sel, MainThreadMarker};
 use objc2_app_kit::{
-    NSAutoresizingMaskOptions, NSBorderType, NSButton, NSScrollView, NSTextField, NSTextView,
-    NSView,
+    NSAutoresizingMaskOptions, NSBorderType, NSButton, NSComboBox, NSLineBreakMode, NSPopUpButton,
+    NSScrollView, NSTextField, NSTextView, NSView,
 };
 use objc2_foundation::{NSNotificationCenter, NSPoint, NSRect, NSSize, NSString};
 
 use crate::popover::popover_view_controller::PopoverViewController;
 
+/// 書き込んだスレッドとお気に入りを候補に出す URL 欄
 fn create_url_text_field(
     mtm: MainThreadMarker,
     target: &PopoverViewController,
-) -> Retained<NSTextField> {
-    let url_field = NSTextField::new(mtm);
+) -> Retained<NSComboBox> {
+    let url_field = NSComboBox::new(mtm);
     url_field.setTranslatesAutoresizingMaskIntoConstraints(false);
     let placeholder = NSString::from_str("掲示板のURLを入力");
     url_field.setPlaceholderString(Some(&placeholder));
+    url_field.setCompletes(true);
+    url_field.setNumberOfVisibleItems(10);
     let delegate = ProtocolObject::from_ref(target);
     unsafe { url_field.setDelegate(Some(delegate)) };
     url_field
 }
 
+fn create_favourite_button(
+    mtm: MainThreadMarker,
+    target: &PopoverViewController,
+) -> Retained<NSButton> {
+    let favourite_button = NSButton::new(mtm);
+    favourite_button.setTranslatesAutoresizingMaskIntoConstraints(false);
+    favourite_button.setTitle(&NSString::from_str("☆"));
+    favourite_button.setButtonType(objc2_app_kit::NSButtonType::MomentaryPushIn);
+    unsafe { favourite_button.setTarget(Some(target)) };
+    unsafe { favourite_button.setAction(Some(sel!(favouriteButtonDidClick:))) };
+    favourite_button
+}
+
+fn create_name_text_field(
+    mtm: MainThreadMarker,
+    target: &PopoverViewController,
+) -> Retained<NSTextField> {
+    let name_field = NSTextField::new(mtm);
+    name_field.setTranslatesAutoresizingMaskIntoConstraints(false);
+    let placeholder = NSString::from_str("名前#トリップキー");
+    name_field.setPlaceholderString(Some(&placeholder));
+    let delegate = ProtocolObject::from_ref(target);
+    unsafe { name_field.setDelegate(Some(delegate)) };
+    name_field
+}
+
+fn create_name_preview_label(mtm: MainThreadMarker) -> Retained<NSTextField> {
+    let name_preview_label = NSTextField::labelWithString(&NSString::from_str(""), mtm);
+    name_preview_label.setTranslatesAutoresizingMaskIntoConstraints(false);
+    name_preview_label.setLineBreakMode(NSLineBreakMode::ByTruncatingTail);
+    name_preview_label.setSelectable(true);
+    name_preview_label
+}
+
+fn create_mail_text_field(
+    mtm: MainThreadMarker,
+    target: &PopoverViewController,
+) -> Retained<NSTextField> {
+    let mail_field = NSTextField::new(mtm);
+    mail_field.setTranslatesAutoresizingMaskIntoConstraints(false);
+    let placeholder = NSString::from_str("メール欄");
+    mail_field.setPlaceholderString(Some(&placeholder));
+    let delegate = ProtocolObject::from_ref(target);
+    unsafe { mail_field.setDelegate(Some(delegate)) };
+    mail_field
+}
+
+fn create_profile_popup(
+    mtm: MainThreadMarker,
+    target: &PopoverViewController,
+) -> Retained<NSPopUpButton> {
+    let profile_popup = NSPopUpButton::new(mtm);
+    profile_popup.setTranslatesAutoresizingMaskIntoConstraints(false);
+    unsafe { profile_popup.setTarget(Some(target)) };
+    unsafe { profile_popup.setAction(Some(sel!(profilePopUpDidChange:))) };
+    profile_popup
+}
+
+fn create_save_profile_button(
+    mtm: MainThreadMarker,
+    target: &PopoverViewController,
+) -> Retained<NSButton> {
+    let save_profile_button = NSButton::new(mtm);
+    save_profile_button.setTranslatesAutoresizingMaskIntoConstraints(false);
+    save_profile_button.setTitle(&NSString::from_str("保存"));
+    save_profile_button.setButtonType(objc2_app_kit::NSButtonType::MomentaryPushIn);
+    unsafe { save_profile_button.setTarget(Some(target)) };
+    unsafe { save_profile_button.setAction(Some(sel!(saveProfileButtonDidClick:))) };
+    save_profile_button
+}
+
+fn create_board_default_button(
+    mtm: MainThreadMarker,
+    target: &PopoverViewController,
+) -> Retained<NSButton> {
+    let board_default_button = NSButton::new(mtm);
+    board_default_button.setTranslatesAutoresizingMaskIntoConstraints(false);
+    board_default_button.setTitle(&NSString::from_str("板既定"));
+    board_default_button.setButtonType(objc2_app_kit::NSButtonType::MomentaryPushIn);
+    unsafe { board_default_button.setTarget(Some(target)) };
+    unsafe { board_default_button.setAction(Some(sel!(boardDefaultButtonDidClick:))) };
+    board_default_button
+}
+
+fn create_delete_profile_button(
+    mtm: MainThreadMarker,
+    target: &PopoverViewController,
+) -> Retained<NSButton> {
+    let delete_profile_button = NSButton::new(mtm);
+    delete_profile_button.setTranslatesAutoresizingMaskIntoConstraints(false);
+    delete_profile_button.setTitle(&NSString::from_str("削除"));
+    delete_profile_button.setButtonType(objc2_app_kit::NSButtonType::MomentaryPushIn);
+    unsafe { delete_profile_button.setTarget(Some(target)) };
+    unsafe { delete_profile_button.setAction(Some(sel!(deleteProfileButtonDidClick:))) };
+    delete_profile_button
+}
+
 fn create_comment_inner_text_view(
     mtm: MainThreadMarker,
     target: &PopoverViewController,
@@ -70,6 +170,46 @@ fn create_sage_checkbox(
     sage_checkbox
 }
 
+fn create_split_checkbox(
+    mtm: MainThreadMarker,
+    target: &PopoverViewController,
+) -> Retained<NSButton> {
+    let split_checkbox = NSButton::new(mtm);
+    split_checkbox.setTranslatesAutoresizingMaskIntoConstraints(false);
+    split_checkbox.setTitle(&NSString::from_str("長文を分割"));
+    split_checkbox.setButtonType(objc2_app_kit::NSButtonType::Switch);
+    unsafe { split_checkbox.setTarget(Some(target)) };
+    unsafe { split_checkbox.setAction(Some(sel!(splitCheckboxDidChange:))) };
+    split_checkbox
+}
+
+fn create_subject_text_field(
+    mtm: MainThreadMarker,
+    target: &PopoverViewController,
+) -> Retained<NSTextField> {
+    let subject_field = NSTextField::new(mtm);
+    subject_field.setTranslatesAutoresizingMaskIntoConstraints(false);
+    let placeholder = NSString::from_str("スレ立てするときのタイトル");
+    subject_field.setPlaceholderString(Some(&placeholder));
+    let delegate = ProtocolObject::from_ref(target);
+    unsafe { subject_field.setDelegate(Some(delegate)) };
+    subject_field
+}
+
+/// 板がスレ立てに対応しているときだけ押せる
+fn create_new_thread_button(
+    mtm: MainThreadMarker,
+    target: &PopoverViewController,
+) -> Retained<NSButton> {
+    let new_thread_button = NSButton::new(mtm);
+    new_thread_button.setTranslatesAutoresizingMaskIntoConstraints(false);
+    new_thread_button.setTitle(&NSString::from_str("スレ立て"));
+    new_thread_button.setButtonType(objc2_app_kit::NSButtonType::MomentaryPushIn);
+    unsafe { new_thread_button.setTarget(Some(target)) };
+    unsafe { new_thread_button.setAction(Some(sel!(newThreadButtonDidClick:))) };
+    new_thread_button
+}
+
 fn create_submit_button(
     mtm: MainThreadMarker,
     target: &PopoverViewController,
@@ -83,6 +223,46 @@ fn create_submit_button(
     submit_button
 }
 
+fn create_outbox_status_label(mtm: MainThreadMarker) -> Retained<NSTextField> {
+    let status_label = NSTextField::labelWithString(&NSString::from_str(""), mtm);
+    status_label.setTranslatesAutoresizingMaskIntoConstraints(false);
+    status_label.setLineBreakMode(NSLineBreakMode::ByTruncatingTail);
+    status_label
+}
+
+fn create_post_state_label(mtm: MainThreadMarker) -> Retained<NSTextField> {
+    let post_state_label = NSTextField::labelWithString(&NSString::from_str(""), mtm);
+    post_state_label.setTranslatesAutoresizingMaskIntoConstraints(false);
+    post_state_label.setLineBreakMode(NSLineBreakMode::ByTruncatingTail);
+    post_state_label
+}
+
+fn create_retry_button(
+    mtm: MainThreadMarker,
+    target: &PopoverViewController,
+) -> Retained<NSButton> {
+    let retry_button = NSButton::new(mtm);
+    retry_button.setTranslatesAutoresizingMaskIntoConstraints(false);
+    retry_button.setTitle(&NSString::from_str("再送"));
+    retry_button.setButtonType(objc2_app_kit::NSButtonType::MomentaryPushIn);
+    unsafe { retry_button.setTarget(Some(target)) };
+    unsafe { retry_button.setAction(Some(sel!(retryButtonDidClick:))) };
+    retry_button
+}
+
+fn create_discard_button(
+    mtm: MainThreadMarker,
+    target: &PopoverViewController,
+) -> Retained<NSButton> {
+    let discard_button = NSButton::new(mtm);
+    discard_button.setTranslatesAutoresizingMaskIntoConstraints(false);
+    discard_button.setTitle(&NSString::from_str("破棄"));
+    discard_button.setButtonType(objc2_app_kit::NSButtonType::MomentaryPushIn);
+    unsafe { discard_button.setTarget(Some(target)) };
+    unsafe { discard_button.setAction(Some(sel!(discardButtonDidClick:))) };
+    discard_button
+}
+
 fn create_close_button(
     mtm: MainThreadMarker,
     target: &PopoverViewController,
@@ -96,11 +276,21 @@ fn create_close_button(
     close_button
 }
 
+#[allow(clippy::too_many_arguments)]
 fn anchor(
     view: &NSView,
     url_field: &NSTextField,
+    favourite_button: &NSButton,
+    mail_field: &NSTextField,
+    subject_field: &NSTextField,
+    new_thread_button: &NSButton,
     scroll_view: &NSScrollView,
+    status_label: &NSTextField,
+    post_state_label: &NSTextField,
+    retry_button: &NSButton,
+    discard_button: &NSButton,
     close_button: &NSButton,
+    split_checkbox: &NSButton,
     sage_checkbox: &NSButton,
     submit_button: &NSButton,
 ) {
@@ -115,17 +305,61 @@ fn anchor(
         .setActive(true);
     url_field
         .trailingAnchor()
-        .constraintEqualToAnchor_constant(&view.trailingAnchor(), -10.0)
+        .constraintEqualToAnchor_constant(&favourite_button.leadingAnchor(), -5.0)
         .setActive(true);
     url_field
         .heightAnchor()
         .constraintEqualToConstant(25.0)
         .setActive(true);
 
+    // Favourite button constraints
+    favourite_button
+        .centerYAnchor()
+        .constraintEqualToAnchor(&url_field.centerYAnchor())
+        .setActive(true);
+    favourite_button
+        .trailingAnchor()
+        .constraintEqualToAnchor_constant(&view.trailingAnchor(), -10.0)
+        .setActive(true);
+    favourite_button
+        .widthAnchor()
+        .constraintEqualToConstant(30.0)
+        .setActive(true);
+
+    // Subject text field constraints
+    subject_field
+        .topAnchor()
+        .constraintEqualToAnchor_constant(&mail_field.bottomAnchor(), 10.0)
+        .setActive(true);
+    subject_field
+        .leadingAnchor()
+        .constraintEqualToAnchor_constant(&view.leadingAnchor(), 10.0)
+        .setActive(true);
+    subject_field
+        .trailingAnchor()
+        .constraintEqualToAnchor_constant(&new_thread_button.leadingAnchor(), -6.0)
+        .setActive(true);
+    subject_field
+        .heightAnchor()
+        .constraintEqualToConstant(25.0)
+        .setActive(true);
+    new_thread_button
+        .centerYAnchor()
+        .constraintEqualToAnchor(&subject_field.centerYAnchor())
+        .setActive(true);
+    new_thread_button
+        .trailingAnchor()
+        .constraintEqualToAnchor_constant(&view.trailingAnchor(), -10.0)
+        .setActive(true);
+    new_thread_button
+        .widthAnchor()
+        .constraintEqualToConstant(80.0)
+        .setActive(true);
+
     // Comment scroll view constraints
     scroll_view
         .topAnchor()
-        .constraintEqualToAnchor_constant(&url_field.bottomAnchor(), 10.0)
+        .constraintEqualToAnchor_constant(&subject_field.bottomAnchor(), 10.0)
         .setActive(true);
     scroll_view
         .leadingAnchor()
@@ -140,6 +374,58 @@ fn anchor(
         .constraintEqualToConstant(100.0)
         .setActive(true);
 
+    // Outbox status constraints
+    status_label
+        .centerYAnchor()
+        .constraintEqualToAnchor(&discard_button.centerYAnchor())
+        .setActive(true);
+    status_label
+        .leadingAnchor()
+        .constraintEqualToAnchor_constant(&view.leadingAnchor(), 10.0)
+        .setActive(true);
+    status_label
+        .trailingAnchor()
+        .constraintEqualToAnchor_constant(&retry_button.leadingAnchor(), -6.0)
+        .setActive(true);
+    retry_button
+        .centerYAnchor()
+        .constraintEqualToAnchor(&discard_button.centerYAnchor())
+        .setActive(true);
+    retry_button
+        .trailingAnchor()
+        .constraintEqualToAnchor_constant(&discard_button.leadingAnchor(), -6.0)
+        .setActive(true);
+    retry_button
+        .widthAnchor()
+        .constraintEqualToConstant(50.0)
+        .setActive(true);
+    discard_button
+        .topAnchor()
+        .constraintEqualToAnchor_constant(&scroll_view.bottomAnchor(), 10.0)
+        .setActive(true);
+    discard_button
+        .trailingAnchor()
+        .constraintEqualToAnchor_constant(&view.trailingAnchor(), -10.0)
+        .setActive(true);
+    discard_button
+        .widthAnchor()
+        .constraintEqualToConstant(50.0)
+        .setActive(true);
+
+    // Post state constraints
+    post_state_label
+        .topAnchor()
+        .constraintEqualToAnchor_constant(&discard_button.bottomAnchor(), 6.0)
+        .setActive(true);
+    post_state_label
+        .leadingAnchor()
+        .constraintEqualToAnchor_constant(&view.leadingAnchor(), 10.0)
+        .setActive(true);
+    post_state_label
+        .trailingAnchor()
+        .constraintEqualToAnchor_constant(&view.trailingAnchor(), -10.0)
+        .setActive(true);
+
     // Close button constraints
     close_button
         .bottomAnchor()
@@ -158,6 +444,20 @@ fn anchor(
         .constraintEqualToConstant(25.0)
         .setActive(true);
 
+    // Split checkbox constraints
+    split_checkbox
+        .bottomAnchor()
+        .constraintEqualToAnchor_constant(&view.bottomAnchor(), -10.0)
+        .setActive(true);
+    split_checkbox
+        .leadingAnchor()
+        .constraintEqualToAnchor_constant(&close_button.trailingAnchor(), 10.0)
+        .setActive(true);
+    split_checkbox
+        .heightAnchor()
+        .constraintEqualToConstant(25.0)
+        .setActive(true);
+
     // Sage checkbox constraints
     sage_checkbox
         .bottomAnchor()
@@ -175,7 +475,7 @@ fn anchor(
     // Submit button constraints
     submit_button
         .topAnchor()
-        .constraintEqualToAnchor_constant(&scroll_view.bottomAnchor(), 10.0)
+        .constraintEqualToAnchor_constant(&post_state_label.bottomAnchor(), 6.0)
         .setActive(true);
     submit_button
         .trailingAnchor()
@@ -191,34 +491,227 @@ fn anchor(
         .setActive(true);
 }
 
-pub fn create_popover_view(
-    mtm: MainThreadMarker,
-    target: &PopoverViewController,
-) -> (Retained<NSView>, Retained<NSTextView>, Retained<NSButton>) {
+#[allow(clippy::too_many_arguments)]
+fn anchor_profile(
+    view: &NSView,
+    url_field: &NSTextField,
+    name_field: &NSTextField,
+    name_preview_label: &NSTextField,
+    mail_field: &NSTextField,
+    profile_popup: &NSPopUpButton,
+    save_profile_button: &NSButton,
+    board_default_button: &NSButton,
+    delete_profile_button: &NSButton,
+) {
+    // Name text field constraints
+    name_field
+        .topAnchor()
+        .constraintEqualToAnchor_constant(&url_field.bottomAnchor(), 10.0)
+        .setActive(true);
+    name_field
+        .leadingAnchor()
+        .constraintEqualToAnchor_constant(&view.leadingAnchor(), 10.0)
+        .setActive(true);
+    name_field
+        .widthAnchor()
+        .constraintEqualToConstant(150.0)
+        .setActive(true);
+    name_field
+        .heightAnchor()
+        .constraintEqualToConstant(25.0)
+        .setActive(true);
+    name_preview_label
+        .centerYAnchor()
+        .constraintEqualToAnchor(&name_field.centerYAnchor())
+        .setActive(true);
+    name_preview_label
+        .leadingAnchor()
+        .constraintEqualToAnchor_constant(&name_field.trailingAnchor(), 8.0)
+        .setActive(true);
+    name_preview_label
+        .trailingAnchor()
+        .constraintEqualToAnchor_constant(&view.trailingAnchor(), -10.0)
+        .setActive(true);
+
+    // Mail text field constraints
+    mail_field
+        .topAnchor()
+        .constraintEqualToAnchor_constant(&name_field.bottomAnchor(), 10.0)
+        .setActive(true);
+    mail_field
+        .leadingAnchor()
+        .constraintEqualToAnchor_constant(&view.leadingAnchor(), 10.0)
+        .setActive(true);
+    mail_field
+        .widthAnchor()
+        .constraintEqualToConstant(100.0)
+        .setActive(true);
+    mail_field
+        .heightAnchor()
+        .constraintEqualToConstant(25.0)
+        .setActive(true);
+
+    // Profile constraints
+    profile_popup
+        .centerYAnchor()
+        .constraintEqualToAnchor(&mail_field.centerYAnchor())
+        .setActive(true);
+    profile_popup
+        .leadingAnchor()
+        .constraintEqualToAnchor_constant(&mail_field.trailingAnchor(), 6.0)
+        .setActive(true);
+    profile_popup
+        .trailingAnchor()
+        .constraintEqualToAnchor_constant(&save_profile_button.leadingAnchor(), -6.0)
+        .setActive(true);
+    save_profile_button
+        .centerYAnchor()
+        .constraintEqualToAnchor(&mail_field.centerYAnchor())
+        .setActive(true);
+    save_profile_button
+        .trailingAnchor()
+        .constraintEqualToAnchor_constant(&board_default_button.leadingAnchor(), -6.0)
+        .setActive(true);
+    save_profile_button
+        .widthAnchor()
+        .constraintEqualToConstant(50.0)
+        .setActive(true);
+    board_default_button
+        .centerYAnchor()
+        .constraintEqualToAnchor(&mail_field.centerYAnchor())
+        .setActive(true);
+    board_default_button
+        .trailingAnchor()
+        .constraintEqualToAnchor_constant(&delete_profile_button.leadingAnchor(), -6.0)
+        .setActive(true);
+    board_default_button
+        .widthAnchor()
+        .constraintEqualToConstant(60.0)
+        .setActive(true);
+    delete_profile_button
+        .centerYAnchor()
+        .constraintEqualToAnchor(&mail_field.centerYAnchor())
+        .setActive(true);
+    delete_profile_button
+        .trailingAnchor()
+        .constraintEqualToAnchor_constant(&view.trailingAnchor(), -10.0)
+        .setActive(true);
+    delete_profile_button
+        .widthAnchor()
+        .constraintEqualToConstant(50.0)
+        .setActive(true);
+}
+
+/// ViewController が後から触るビュー
+pub struct PopoverViews {
+    pub view: Retained<NSView>,
+    pub url_field: Retained<NSComboBox>,
+    pub favourite_button: Retained<NSButton>,
+    pub name_field: Retained<NSTextField>,
+    pub name_preview_label: Retained<NSTextField>,
+    pub mail_field: Retained<NSTextField>,
+    pub profile_popup: Retained<NSPopUpButton>,
+    pub subject_field: Retained<NSTextField>,
+    pub new_thread_button: Retained<NSButton>,
+    pub comment_text_view: Retained<NSTextView>,
+    pub split_checkbox: Retained<NSButton>,
+    pub sage_checkbox: Retained<NSButton>,
+    pub status_label: Retained<NSTextField>,
+    pub post_state_label: Retained<NSTextField>,
+    pub submit_button: Retained<NSButton>,
+}
+
+pub fn create_popover_view(mtm: MainThreadMarker, target: &PopoverViewController) -> PopoverViews {
     let view = NSView::new(mtm);
-    let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(300.0, 200.0));
+    let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(400.0, 365.0));
     view.setFrame(frame);
 
     let url_field = create_url_text_field(mtm, target);
+    let favourite_button = create_favourite_button(mtm, target);
+    let name_field = create_name_text_field(mtm, target);
+    let name_preview_label = create_name_preview_label(mtm);
+    let mail_field = create_mail_text_field(mtm, target);
+    let profile_popup = create_profile_popup(mtm, target);
+    let save_profile_button = create_save_profile_button(mtm, target);
+    let board_default_button = create_board_default_button(mtm, target);
+    let delete_profile_button = create_delete_profile_button(mtm, target);
+    let subject_field = create_subject_text_field(mtm, target);
+    let new_thread_button = create_new_thread_button(mtm, target);
     let (scroll_view, comment_text_view) = create_comment_text_view(mtm, target);
+    let split_checkbox = create_split_checkbox(mtm, target);
     let sage_checkbox = create_sage_checkbox(mtm, target);
     let submit_button = create_submit_button(mtm, target);
+    let status_label = create_outbox_status_label(mtm);
+    let post_state_label = create_post_state_label(mtm);
+    let retry_button = create_retry_button(mtm, target);
+    let discard_button = create_discard_button(mtm, target);
     let close_button = create_close_button(mtm, target);
 
     view.addSubview(&url_field);
+    view.addSubview(&favourite_button);
+    view.addSubview(&name_field);
+    view.addSubview(&name_preview_label);
+    view.addSubview(&mail_field);
+    view.addSubview(&profile_popup);
+    view.addSubview(&save_profile_button);
+    view.addSubview(&board_default_button);
+    view.addSubview(&delete_profile_button);
+    view.addSubview(&subject_field);
+    view.addSubview(&new_thread_button);
     view.addSubview(&scroll_view);
+    view.addSubview(&status_label);
+    view.addSubview(&post_state_label);
+    view.addSubview(&retry_button);
+    view.addSubview(&discard_button);
     view.addSubview(&close_button);
+    view.addSubview(&split_checkbox);
     view.addSubview(&sage_checkbox);
     view.addSubview(&submit_button);
 
     anchor(
         &view,
         &url_field,
+        &favourite_button,
+        &mail_field,
+        &subject_field,
+        &new_thread_button,
         &scroll_view,
+        &status_label,
+        &post_state_label,
+        &retry_button,
+        &discard_button,
         &close_button,
+        &split_checkbox,
         &sage_checkbox,
         &submit_button,
     );
+    anchor_profile(
+        &view,
+        &url_field,
+        &name_field,
+        &name_preview_label,
+        &mail_field,
+        &profile_popup,
+        &save_profile_button,
+        &board_default_button,
+        &delete_profile_button,
+    );
 
-    (view, comment_text_view, sage_checkbox)
+    PopoverViews {
+        view,
+        url_field,
+        favourite_button,
+        name_field,
+        name_preview_label,
+        mail_field,
+        profile_popup,
+        subject_field,
+        new_thread_button,
+        comment_text_view,
+        split_checkbox,
+        sage_checkbox,
+        status_label,
+        post_state_label,
+        submit_button,
+    }
 }
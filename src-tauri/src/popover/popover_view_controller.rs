@@ -1,28 +1,57 @@
-use std::cell::{OnceCell, RefCell};
+use std::{
+    cell::{OnceCell, RefCell},
+    sync::Arc,
+};
 
-use crate::popover::popover_view_model::PopoverViewModel;
+use crate::popover::popover_view_model::{
+    PopoverViewModel, PostState, ProfileState, UrlCompletionState,
+};
 
 use super::popover_view::create_popover_view;
-use dispatch2::{run_on_main, MainThreadBound};
+use dispatch2::{run_on_main, DispatchQueue, MainThreadBound};
 use objc2::{define_class, msg_send, rc::Retained, DefinedClass, MainThreadMarker, MainThreadOnly};
 use objc2_app_kit::{
-    NSApplication, NSButton, NSControlTextEditingDelegate, NSTextField, NSTextFieldDelegate,
-    NSTextView, NSViewController,
+    NSApplication, NSButton, NSComboBox, NSComboBoxDelegate, NSControlTextEditingDelegate,
+    NSPopUpButton, NSTextField, NSTextFieldDelegate, NSTextView, NSViewController,
 };
 use objc2_foundation::{NSNotification, NSObject, NSObjectProtocol, NSString};
 
 pub struct PopoverViewControllerIvars {
     view_model: RefCell<PopoverViewModel>,
+    url_field: OnceCell<Retained<NSComboBox>>,
+    favourite_button: OnceCell<Retained<NSButton>>,
+    name_field: OnceCell<Retained<NSTextField>>,
+    name_preview_label: OnceCell<Retained<NSTextField>>,
+    mail_field: OnceCell<Retained<NSTextField>>,
+    profile_popup: OnceCell<Retained<NSPopUpButton>>,
+    subject_field: OnceCell<Retained<NSTextField>>,
+    new_thread_button: OnceCell<Retained<NSButton>>,
     text_view: OnceCell<Retained<NSTextView>>,
+    split_checkbox: OnceCell<Retained<NSButton>>,
     sage_checkbox: OnceCell<Retained<NSButton>>,
+    status_label: OnceCell<Retained<NSTextField>>,
+    post_state_label: OnceCell<Retained<NSTextField>>,
+    submit_button: OnceCell<Retained<NSButton>>,
 }
 
 impl Default for PopoverViewControllerIvars {
     fn default() -> Self {
         Self {
             view_model: RefCell::new(PopoverViewModel::new()),
+            url_field: OnceCell::new(),
+            favourite_button: OnceCell::new(),
+            name_field: OnceCell::new(),
+            name_preview_label: OnceCell::new(),
+            mail_field: OnceCell::new(),
+            profile_popup: OnceCell::new(),
+            subject_field: OnceCell::new(),
+            new_thread_button: OnceCell::new(),
             text_view: OnceCell::new(),
+            split_checkbox: OnceCell::new(),
             sage_checkbox: OnceCell::new(),
+            status_label: OnceCell::new(),
+            post_state_label: OnceCell::new(),
+            submit_button: OnceCell::new(),
         }
     }
 }
@@ -38,12 +67,19 @@ define_class!(
     unsafe impl NSControlTextEditingDelegate for PopoverViewController {
         #[unsafe(method(controlTextDidChange:))]
         fn control_text_did_change(&self, notification: &NSNotification) {
-            self.url_text_did_change(notification);
+            self.control_text_did_change_impl(notification);
         }
     }
 
     unsafe impl NSTextFieldDelegate for PopoverViewController {}
 
+    unsafe impl NSComboBoxDelegate for PopoverViewController {
+        #[unsafe(method(comboBoxSelectionDidChange:))]
+        fn combo_box_selection_did_change(&self, _notification: &NSNotification) {
+            self.combo_box_selection_did_change_impl();
+        }
+    }
+
     impl PopoverViewController {
         #[unsafe(method(loadView))]
         fn load_view(&self) {
@@ -56,16 +92,70 @@ define_class!(
             self.post_button_did_click_impl();
         }
 
+        #[unsafe(method(newThreadButtonDidClick:))]
+        fn new_thread_button_did_click(&self, _sender: &NSObject) {
+            self.ivars().view_model.borrow_mut().on_create_thread_clicked();
+        }
+
         #[unsafe(method(sageCheckboxDidChange:))]
         fn sage_checkbox_did_change(&self, _sender: &NSButton) {
             self.sage_checkbox_did_change_impl();
         }
 
+        #[unsafe(method(splitCheckboxDidChange:))]
+        fn split_checkbox_did_change(&self, _sender: &NSButton) {
+            self.split_checkbox_did_change_impl();
+        }
+
         #[unsafe(method(commentTextViewDidChange:))]
         fn comment_text_view_did_change(&self, notification: &NSNotification) {
             self.comment_text_view_did_change_impl(notification);
         }
 
+        #[unsafe(method(retryButtonDidClick:))]
+        fn retry_button_did_click(&self, _sender: &NSObject) {
+            self.ivars().view_model.borrow_mut().on_retry_clicked();
+        }
+
+        #[unsafe(method(discardButtonDidClick:))]
+        fn discard_button_did_click(&self, _sender: &NSObject) {
+            self.ivars().view_model.borrow_mut().on_discard_clicked();
+        }
+
+        #[unsafe(method(favouriteButtonDidClick:))]
+        fn favourite_button_did_click(&self, _sender: &NSObject) {
+            self.ivars()
+                .view_model
+                .borrow_mut()
+                .on_toggle_favourite_clicked();
+        }
+
+        #[unsafe(method(profilePopUpDidChange:))]
+        fn profile_popup_did_change(&self, _sender: &NSPopUpButton) {
+            self.profile_popup_did_change_impl();
+        }
+
+        #[unsafe(method(saveProfileButtonDidClick:))]
+        fn save_profile_button_did_click(&self, _sender: &NSObject) {
+            self.ivars().view_model.borrow_mut().on_save_profile_clicked();
+        }
+
+        #[unsafe(method(boardDefaultButtonDidClick:))]
+        fn board_default_button_did_click(&self, _sender: &NSObject) {
+            self.ivars()
+                .view_model
+                .borrow_mut()
+                .on_make_board_default_clicked();
+        }
+
+        #[unsafe(method(deleteProfileButtonDidClick:))]
+        fn delete_profile_button_did_click(&self, _sender: &NSObject) {
+            self.ivars()
+                .view_model
+                .borrow_mut()
+                .on_delete_profile_clicked();
+        }
+
         #[unsafe(method(closeButtonDidClick:))]
         fn close_button_did_click(&self, _sender: &NSObject) {
             let mtm = MainThreadMarker::new().unwrap();
@@ -74,6 +164,47 @@ define_class!(
     }
 );
 
+/// プロファイルの内容を入力欄と一覧に反映する
+fn show_profile(
+    state: &ProfileState,
+    name_field: &NSTextField,
+    name_preview_label: &NSTextField,
+    mail_field: &NSTextField,
+    sage_checkbox: &NSButton,
+    profile_popup: &NSPopUpButton,
+) {
+    name_field.setStringValue(&NSString::from_str(&state.profile.name));
+    name_preview_label.setStringValue(&NSString::from_str(&state.name_preview));
+    mail_field.setStringValue(&NSString::from_str(&state.profile.mail));
+    sage_checkbox.setState(if state.profile.sage { 1 } else { 0 });
+    profile_popup.removeAllItems();
+    for label in &state.labels {
+        profile_popup.addItemWithTitle(&NSString::from_str(label));
+    }
+    profile_popup.selectItemWithTitle(&NSString::from_str(&state.profile.label));
+}
+
+/// URL 欄の候補とお気に入りの印を反映する
+fn show_url_completions(
+    state: &UrlCompletionState,
+    url_field: &NSComboBox,
+    favourite_button: &NSButton,
+) {
+    url_field.removeAllItems();
+    for completion in &state.completions {
+        let label = NSString::from_str(&completion.label);
+        unsafe { url_field.addItemWithObjectValue(&label) };
+    }
+    let title = if state.favourite { "★" } else { "☆" };
+    favourite_button.setTitle(&NSString::from_str(title));
+}
+
+/// 書き込みの状態を一行で出し、送信中は書き込みボタンを押せなくする
+fn show_post_state(state: &PostState, post_state_label: &NSTextField, submit_button: &NSButton) {
+    post_state_label.setStringValue(&NSString::from_str(&state.status()));
+    submit_button.setEnabled(!state.is_busy());
+}
+
 impl PopoverViewController {
     pub fn new(mtm: MainThreadMarker) -> Retained<Self> {
         let this = Self::alloc(mtm).set_ivars(PopoverViewControllerIvars::default());
@@ -81,20 +212,157 @@ impl PopoverViewController {
     }
 
     fn load_view_impl(&self, mtm: MainThreadMarker) {
-        let (view, text_view, sage_checkbox) = create_popover_view(mtm, self);
+        let views = create_popover_view(mtm, self);
 
-        self.ivars().text_view.set(text_view).unwrap();
+        self.ivars().url_field.set(views.url_field.clone()).unwrap();
+        self.ivars()
+            .favourite_button
+            .set(views.favourite_button.clone())
+            .unwrap();
+        self.ivars()
+            .name_field
+            .set(views.name_field.clone())
+            .unwrap();
+        self.ivars()
+            .name_preview_label
+            .set(views.name_preview_label.clone())
+            .unwrap();
+        self.ivars()
+            .mail_field
+            .set(views.mail_field.clone())
+            .unwrap();
+        self.ivars()
+            .profile_popup
+            .set(views.profile_popup.clone())
+            .unwrap();
+        self.ivars()
+            .subject_field
+            .set(views.subject_field.clone())
+            .unwrap();
+        self.ivars()
+            .new_thread_button
+            .set(views.new_thread_button.clone())
+            .unwrap();
+        self.ivars()
+            .text_view
+            .set(views.comment_text_view.clone())
+            .unwrap();
+        self.ivars()
+            .split_checkbox
+            .set(views.split_checkbox.clone())
+            .unwrap();
         self.ivars()
             .sage_checkbox
-            .set(sage_checkbox.clone())
+            .set(views.sage_checkbox.clone())
             .unwrap();
-        self.setView(&view);
+        self.ivars().status_label.set(views.status_label).unwrap();
+        self.ivars()
+            .post_state_label
+            .set(views.post_state_label.clone())
+            .unwrap();
+        self.ivars()
+            .submit_button
+            .set(views.submit_button.clone())
+            .unwrap();
+        self.setView(&views.view);
 
         // ViewModelの初期値をビューに反映
-        let initial_sage = self.ivars().view_model.borrow().get_sage();
-        sage_checkbox.setState(if initial_sage { 1 } else { 0 });
+        let initial_url = self.ivars().view_model.borrow().get_url();
+        views
+            .url_field
+            .setStringValue(&NSString::from_str(&initial_url));
+        let initial_completions = self.ivars().view_model.borrow().get_url_completion_state();
+        show_url_completions(
+            &initial_completions,
+            &views.url_field,
+            &views.favourite_button,
+        );
+        self.update_new_thread_button();
+        let initial_comment = self.ivars().view_model.borrow().get_comment();
+        views
+            .comment_text_view
+            .setString(&NSString::from_str(&initial_comment));
+        let initial_profile = self.ivars().view_model.borrow().get_profile_state();
+        show_profile(
+            &initial_profile,
+            &views.name_field,
+            &views.name_preview_label,
+            &views.mail_field,
+            &views.sage_checkbox,
+            &views.profile_popup,
+        );
+        let initial_post_state = self.ivars().view_model.borrow().get_post_state();
+        show_post_state(
+            &initial_post_state,
+            &views.post_state_label,
+            &views.submit_button,
+        );
+        let initial_split = self.ivars().view_model.borrow().get_split();
+        views
+            .split_checkbox
+            .setState(if initial_split { 1 } else { 0 });
 
+        self.subscribe_to_url_changes(mtm);
         self.subscribe_to_comment_changes(mtm);
+        self.subscribe_to_outbox_changes(mtm);
+        self.subscribe_to_profile_changes(mtm);
+        self.subscribe_to_url_completion_changes(mtm);
+        self.subscribe_to_post_state_changes(mtm);
+    }
+
+    fn subscribe_to_post_state_changes(&self, mtm: MainThreadMarker) {
+        let ivars = self.ivars();
+        let views = (
+            ivars.post_state_label.get().unwrap().clone(),
+            ivars.submit_button.get().unwrap().clone(),
+        );
+        let mtb = MainThreadBound::new(views, mtm);
+
+        ivars
+            .view_model
+            .borrow_mut()
+            .subscribe_post_state(move |state| {
+                run_on_main(|mtm| {
+                    let (post_state_label, submit_button) = mtb.get(mtm);
+                    show_post_state(&state, post_state_label, submit_button);
+                });
+            });
+    }
+
+    fn subscribe_to_url_completion_changes(&self, mtm: MainThreadMarker) {
+        let ivars = self.ivars();
+        let views = (
+            ivars.url_field.get().unwrap().clone(),
+            ivars.favourite_button.get().unwrap().clone(),
+        );
+        let mtb = Arc::new(MainThreadBound::new(views, mtm));
+
+        ivars
+            .view_model
+            .borrow_mut()
+            .subscribe_url_completions(move |state| {
+                // 送信のタスクからも呼ばれるので、メインスレッドを待たずに反映する
+                let mtb = mtb.clone();
+                DispatchQueue::main().exec_async(move || {
+                    let mtm = MainThreadMarker::new().unwrap();
+                    let (url_field, favourite_button) = mtb.get(mtm);
+                    show_url_completions(&state, url_field, favourite_button);
+                });
+            });
+    }
+
+    fn subscribe_to_url_changes(&self, mtm: MainThreadMarker) {
+        let url_field = self.ivars().url_field.get().unwrap().clone();
+        let mtb = MainThreadBound::new(url_field, mtm);
+
+        self.ivars()
+            .view_model
+            .borrow_mut()
+            .subscribe_url(move |url| {
+                run_on_main(|mtm| {
+                    mtb.get(mtm).setStringValue(&NSString::from_str(&url));
+                });
+            });
     }
 
     fn subscribe_to_comment_changes(&self, mtm: MainThreadMarker) {
@@ -114,6 +382,51 @@ impl PopoverViewController {
                 });
             });
     }
+
+    fn subscribe_to_outbox_changes(&self, mtm: MainThreadMarker) {
+        let status_label = self.ivars().status_label.get().unwrap().clone();
+        let mtb = MainThreadBound::new(status_label, mtm);
+
+        self.ivars()
+            .view_model
+            .borrow_mut()
+            .subscribe_outbox_status(move |status| {
+                run_on_main(|mtm| {
+                    mtb.get(mtm).setStringValue(&NSString::from_str(&status));
+                });
+            });
+    }
+
+    fn subscribe_to_profile_changes(&self, mtm: MainThreadMarker) {
+        let ivars = self.ivars();
+        let views = (
+            ivars.name_field.get().unwrap().clone(),
+            ivars.name_preview_label.get().unwrap().clone(),
+            ivars.mail_field.get().unwrap().clone(),
+            ivars.sage_checkbox.get().unwrap().clone(),
+            ivars.profile_popup.get().unwrap().clone(),
+        );
+        let mtb = MainThreadBound::new(views, mtm);
+
+        ivars
+            .view_model
+            .borrow_mut()
+            .subscribe_profile(move |state| {
+                run_on_main(|mtm| {
+                    let (name_field, name_preview_label, mail_field, sage_checkbox, profile_popup) =
+                        mtb.get(mtm);
+                    show_profile(
+                        &state,
+                        name_field,
+                        name_preview_label,
+                        mail_field,
+                        sage_checkbox,
+                        profile_popup,
+                    );
+                });
+            });
+    }
+
     fn post_button_did_click_impl(&self) {
         self.ivars().view_model.borrow_mut().on_post_clicked();
     }
@@ -125,12 +438,78 @@ impl PopoverViewController {
         self.ivars().view_model.borrow_mut().set_sage(sage);
     }
 
-    fn url_text_did_change(&self, notification: &NSNotification) {
+    fn split_checkbox_did_change_impl(&self) {
+        let checkbox = self.ivars().split_checkbox.get().unwrap();
+        let split = checkbox.state() == 1; // NSControlStateValue::On
+        self.ivars().view_model.borrow_mut().set_split(split);
+    }
+
+    fn control_text_did_change_impl(&self, notification: &NSNotification) {
         let object = notification.object().unwrap();
         let text_field = object.downcast::<NSTextField>().unwrap();
         let text = text_field.stringValue();
         let text_str = text.to_string();
-        self.ivars().view_model.borrow_mut().set_url(text_str);
+        let mut view_model = self.ivars().view_model.borrow_mut();
+        if self.ivars().mail_field.get() == Some(&text_field) {
+            view_model.set_mail(text_str);
+            return;
+        }
+        if self.ivars().subject_field.get() == Some(&text_field) {
+            view_model.set_subject(text_str);
+            return;
+        }
+        if self.ivars().name_field.get() == Some(&text_field) {
+            view_model.set_name(text_str);
+        } else {
+            view_model.set_url(text_str);
+            let new_thread_button = self.ivars().new_thread_button.get().unwrap();
+            new_thread_button.setEnabled(view_model.can_create_thread());
+        }
+        // トリップは板の文字コードで変わるので URL が変わっても出し直す
+        let preview = NSString::from_str(&view_model.name_preview());
+        let label = self.ivars().name_preview_label.get().unwrap();
+        label.setStringValue(&preview);
+    }
+
+    fn combo_box_selection_did_change_impl(&self) {
+        let url_field = self.ivars().url_field.get().unwrap();
+        let Ok(index) = usize::try_from(url_field.indexOfSelectedItem()) else {
+            return;
+        };
+        let Some(url) = self
+            .ivars()
+            .view_model
+            .borrow_mut()
+            .on_url_completion_selected(index)
+        else {
+            return;
+        };
+        self.update_new_thread_button();
+        // 選んだ直後は候補の文字列が入るので、その後で URL に置き換える
+        let mtm = MainThreadMarker::new().unwrap();
+        let mtb = MainThreadBound::new(url_field.clone(), mtm);
+        DispatchQueue::main().exec_async(move || {
+            let mtm = MainThreadMarker::new().unwrap();
+            mtb.get(mtm).setStringValue(&NSString::from_str(&url));
+        });
+    }
+
+    /// 今の URL の板がスレ立てに対応しているときだけスレ立てボタンを押せるようにする
+    fn update_new_thread_button(&self) {
+        let enabled = self.ivars().view_model.borrow().can_create_thread();
+        let button = self.ivars().new_thread_button.get().unwrap();
+        button.setEnabled(enabled);
+    }
+
+    fn profile_popup_did_change_impl(&self) {
+        let popup = self.ivars().profile_popup.get().unwrap();
+        if let Some(label) = popup.titleOfSelectedItem() {
+            let label = label.to_string();
+            self.ivars()
+                .view_model
+                .borrow_mut()
+                .on_profile_selected(&label);
+        }
     }
 
     fn comment_text_view_did_change_impl(&self, notification: &NSNotification) {
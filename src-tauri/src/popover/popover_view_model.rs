@@ -1,69 +1,876 @@
-use anyhow::Ok;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use encoding_rs::{Encoding, SHIFT_JIS};
 use tauri::async_runtime::spawn;
+use tracing::{info, warn};
 use url::Url;
 
-use crate::bbs::{self, fetch_thread_url_encoding_name, parse_bbs_url};
+use crate::{
+    app_dir::config_dir,
+    bbs::{
+        self, capabilities, encoding_for_label, fetch_thread_url_encoding_name, find_unmappable,
+        is_safe_to_resend, name_with_trip, parse_bbs_url, post_following_next_thread, post_parts,
+        set_unmappable_policy, split_message, BbsClient, BbsClientConfig, BbsError, BoardSettings,
+        NextThread, RetryPolicy, RetryProgress, Unmappable, UnmappablePolicy,
+    },
+    outbox::{retry_wait, Outbox, OutboxEntry, SendError},
+    profile::PostingProfile,
+    settings::{Settings, SettingsStore},
+};
+
+type ProfileObserver = Option<Box<dyn Fn(ProfileState) + Send + 'static>>;
+/// 送信のタスクからも呼ぶので、取り出してロックを外してから呼ぶ
+type SharedObserver<T> = Mutex<Option<Arc<dyn Fn(T) + Send + Sync + 'static>>>;
+
+fn notify<T>(observer: &SharedObserver<T>, value: T) {
+    let observer = observer.lock().unwrap().clone();
+    if let Some(observer) = observer {
+        observer(value);
+    }
+}
+
+/// 書き込みボタンを押してからの状態
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum PostState {
+    #[default]
+    Idle,
+    /// 書き込むスレッドと文字コードを調べている
+    ResolvingThread,
+    Posting,
+    /// 書き込めなかったので `wait` だけ待って送り直す
+    Retrying {
+        wait: Duration,
+    },
+    /// 板の文字コードで表せない文字がある。拒否しないならもう一度押すと書き込む
+    Unmappable {
+        characters: Vec<Unmappable>,
+        refused: bool,
+    },
+    Succeeded {
+        thread_title: String,
+        /// 次スレに移動して書き込んだ
+        moved: bool,
+    },
+    /// スレッドを立てて URL 欄をそのスレッドにした
+    CreatedThread {
+        thread_title: String,
+    },
+    /// 送り直しても通らない失敗。送れなかった本文を返す
+    Failed {
+        error: String,
+        original_comment: String,
+    },
+}
+
+impl PostState {
+    /// 送信中は続けて押せないようにする
+    pub fn is_busy(&self) -> bool {
+        matches!(self, PostState::ResolvingThread | PostState::Posting)
+    }
+
+    /// 状態を一行で表す。何もしていなければ空
+    pub fn status(&self) -> String {
+        match self {
+            PostState::Idle => String::new(),
+            PostState::ResolvingThread => "スレッドを確認しています".to_owned(),
+            PostState::Posting => "書き込んでいます".to_owned(),
+            PostState::Retrying { wait } => format!("{} 秒後に送り直します", wait.as_secs()),
+            PostState::Unmappable {
+                characters,
+                refused: true,
+            } => format!(
+                "板の文字コードで表せない文字があります: {}",
+                characters
+                    .iter()
+                    .map(|x| x.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            PostState::Unmappable {
+                characters,
+                refused: false,
+            } => format!(
+                "もう一度押すと置き換えて書き込みます: {}",
+                characters
+                    .iter()
+                    .map(|x| format!(
+                        "{}→{}",
+                        x.text,
+                        x.replacement.as_deref().unwrap_or_default()
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            PostState::Succeeded {
+                thread_title,
+                moved: false,
+            } => format!("書き込みました: {}", thread_title),
+            PostState::Succeeded {
+                thread_title,
+                moved: true,
+            } => format!("次スレに移動しました: {}", thread_title),
+            PostState::CreatedThread { thread_title } => {
+                format!("スレッドを立てました: {}", thread_title)
+            }
+            PostState::Failed { error, .. } => format!("書き込めませんでした: {}", error),
+        }
+    }
+}
+
+/// プロファイルを切り替えたときにビューへ反映するもの
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileState {
+    pub profile: PostingProfile,
+    pub name_preview: String,
+    pub labels: Vec<String>,
+}
+
+/// URL 欄に出す候補。お気に入りが先で、その後に書き込んだ新しい順
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlCompletion {
+    /// 候補の一覧に出す文字列
+    pub label: String,
+    pub url: String,
+    pub favourite: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlCompletionState {
+    pub completions: Vec<UrlCompletion>,
+    /// 今の URL がお気に入りか
+    pub favourite: bool,
+}
+
+fn url_completions(settings: &Settings) -> Vec<UrlCompletion> {
+    let label = |title: &str, url: &str| {
+        if title.is_empty() {
+            url.to_owned()
+        } else {
+            format!("{} - {}", title, url)
+        }
+    };
+    let favourites = settings.favourite_threads.iter().map(|x| UrlCompletion {
+        label: format!("★ {}", label(&x.title, &x.url)),
+        url: x.url.clone(),
+        favourite: true,
+    });
+    let recents = settings
+        .recent_threads
+        .iter()
+        .filter(|x| !settings.is_favourite(&x.url))
+        .map(|x| UrlCompletion {
+            label: label(&x.title, &x.url),
+            url: x.url.clone(),
+            favourite: false,
+        });
+    favourites.chain(recents).collect()
+}
+
+fn url_completion_state(settings: &Settings, url: &str) -> UrlCompletionState {
+    UrlCompletionState {
+        completions: url_completions(settings),
+        favourite: settings.is_favourite(url),
+    }
+}
+
+/// 分けた書き込みの間隔。連投規制に掛かったときはさらに待つ
+const PART_INTERVAL: Duration = Duration::from_secs(10);
+/// 送信待ちを送り直すまでの最初の間隔
+const OUTBOX_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 書き込んだスレッド
+struct PostedThread {
+    url: Url,
+    title: String,
+    charset: String,
+    /// 次スレに移動した
+    moved: bool,
+}
+
+impl PostedThread {
+    fn new(url: Url, title: String, charset: String, next_thread: Option<NextThread>) -> Self {
+        match next_thread {
+            Some(next) => Self {
+                url: next.url,
+                title: next.title,
+                charset,
+                moved: true,
+            },
+            None => Self {
+                url,
+                title,
+                charset,
+                moved: false,
+            },
+        }
+    }
+}
+
+/// 書き込んだスレッドを返す。スレッドが分かったら `on_resolved` を、待って送り直すときは `on_retry` を呼ぶ。
+/// 分けて書き込むときは分けた本文と書き込み済みの件数を `outbox` に残す
+async fn send_entry(
+    client: &BbsClient,
+    retry: &RetryPolicy,
+    outbox: &Outbox,
+    entry: OutboxEntry,
+    on_resolved: &(dyn Fn() + Send + Sync),
+    on_retry: &(dyn Fn(RetryProgress) + Send + Sync),
+) -> Result<PostedThread, BbsError> {
+    let url = Url::parse(&entry.url).map_err(|_| BbsError::InvalidUrl(entry.url.clone()))?;
+    let bbs_url = parse_bbs_url(client, url).map_err(|x| BbsError::InvalidUrl(x.to_string()))?;
+    let (thread_url, encoding, title) = fetch_thread_url_encoding_name(client, &bbs_url).await?;
+    on_resolved();
+    let bbs = bbs::new(client, &thread_url)?;
+
+    if entry.split {
+        let parts = if entry.parts.is_empty() {
+            let settings = if capabilities(&thread_url).is_some_and(|x| x.board_settings) {
+                bbs.board().board_settings().await.unwrap_or_default()
+            } else {
+                BoardSettings::default()
+            };
+            let parts = split_message(encoding_for_label(&encoding)?, &settings, &entry.comment);
+            outbox.set_parts(entry.id, parts.clone());
+            parts
+        } else {
+            entry.parts.clone()
+        };
+        let next_thread = post_parts(
+            bbs.as_ref(),
+            &encoding,
+            &entry.name,
+            &entry.email,
+            &parts,
+            entry.posted_parts,
+            PART_INTERVAL,
+            retry,
+            on_retry,
+            &|posted| outbox.set_posted_parts(entry.id, posted),
+        )
+        .await?;
+        return Ok(PostedThread::new(thread_url, title, encoding, next_thread));
+    }
+
+    let (outcome, next_thread) = post_following_next_thread(
+        bbs.as_ref(),
+        &encoding,
+        &entry.name,
+        &entry.email,
+        &entry.comment,
+        retry,
+        on_retry,
+    )
+    .await?;
+    outcome.into_result()?;
+    Ok(PostedThread::new(thread_url, title, encoding, next_thread))
+}
+
+/// 送信待ちの状況を一行で表す。無ければ空
+fn outbox_status(entries: &[OutboxEntry]) -> String {
+    let Some(head) = entries.first() else {
+        return String::new();
+    };
+    let state = if head.held {
+        "送信を止めています"
+    } else {
+        "送信待ち"
+    };
+    match &head.last_error {
+        Some(err) => format!("{} {} 件: {}", state, entries.len(), err),
+        None => format!("{} {} 件", state, entries.len()),
+    }
+}
+
+/// 入力中の URL の板の文字コード。前に書き込めたときのもので、分からなければ Shift_JIS とみなす
+fn board_encoding(settings: &Settings) -> &'static Encoding {
+    settings
+        .board_charset(&settings.url)
+        .and_then(|x| Encoding::for_label(x.as_bytes()))
+        .unwrap_or(SHIFT_JIS)
+}
+
+/// 名前欄のトリップキーを除いた部分をプロファイルの名前にする
+fn profile_label(name: &str) -> String {
+    let handle = name.split('#').next().unwrap_or_default().trim();
+    if handle.is_empty() {
+        PostingProfile::default().label
+    } else {
+        handle.to_owned()
+    }
+}
+
+/// 送信のタスクと共有するもの。本文は設定の `draft` を正とする
+struct Shared {
+    settings: Mutex<SettingsStore>,
+    comment_observer: SharedObserver<String>,
+    url_observer: SharedObserver<String>,
+    url_completion_observer: SharedObserver<UrlCompletionState>,
+    post_state: Mutex<PostState>,
+    post_state_observer: SharedObserver<PostState>,
+    /// 作ったときの通信設定とクライアント
+    client: Mutex<Option<(BbsClientConfig, BbsClient)>>,
+}
+
+impl Shared {
+    fn comment(&self) -> String {
+        self.settings.lock().unwrap().get().draft.clone()
+    }
+
+    fn set_comment(&self, comment: String) {
+        self.settings
+            .lock()
+            .unwrap()
+            .update(|x| x.draft.clone_from(&comment));
+        notify(&self.comment_observer, comment);
+    }
+
+    /// 書き込んだスレッドから次スレに移ったら、URL 欄がそのままなら移動先に変える
+    fn follow_next_thread(&self, from: &str, to: &Url) {
+        let moved = {
+            let mut settings = self.settings.lock().unwrap();
+            let moved = settings.get().url == from;
+            if moved {
+                settings.update(|x| x.url = to.to_string());
+            }
+            moved
+        };
+        if moved {
+            notify(&self.url_observer, to.to_string());
+        }
+    }
+
+    /// URL 欄を `url` に変える
+    fn set_url(&self, url: &Url) {
+        self.settings
+            .lock()
+            .unwrap()
+            .update(|x| x.url = url.to_string());
+        notify(&self.url_observer, url.to_string());
+    }
 
-type CommentObserver = Option<Box<dyn Fn(String) + Send + 'static>>;
+    /// 設定の通信設定で作ったクライアント。設定が変わっていたら作り直す
+    fn client(&self) -> Result<BbsClient, BbsError> {
+        let config = self
+            .settings
+            .lock()
+            .unwrap()
+            .get()
+            .connection
+            .client_config();
+        let mut client = self.client.lock().unwrap();
+        if let Some((_, client)) = client.as_ref().filter(|(x, _)| *x == config) {
+            return Ok(client.clone());
+        }
+        let new_client = BbsClient::new(&config)?;
+        *client = Some((config, new_client.clone()));
+        Ok(new_client)
+    }
 
+    fn set_post_state(&self, state: PostState) {
+        *self.post_state.lock().unwrap() = state.clone();
+        notify(&self.post_state_observer, state);
+    }
+
+    /// 書き込めたスレッドと板の文字コードを履歴に残して候補を知らせる
+    fn record_post(&self, url: &Url, title: &str, charset: &str) {
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or_default();
+        let state = {
+            let mut settings = self.settings.lock().unwrap();
+            settings.update(|x| {
+                x.record_post(url.as_str(), title, at);
+                x.set_board_charset(url.as_str(), charset);
+            });
+            // 設定の URL は入力中の URL
+            let settings = settings.get();
+            url_completion_state(settings, &settings.url)
+        };
+        notify(&self.url_completion_observer, state);
+    }
+
+    /// 送り直せない失敗なら止める。書き込んでいないと分かっていて入力欄が空なら、本文を戻して送信待ちから外す
+    fn fail(&self, entry: &OutboxEntry, err: BbsError) -> SendError {
+        if is_safe_to_resend(&err) {
+            // 送信待ちに残して送り直す
+            self.set_post_state(PostState::Retrying {
+                wait: retry_wait(OUTBOX_RETRY_INTERVAL, entry.attempts),
+            });
+            return SendError::Keep(err);
+        }
+        self.set_post_state(PostState::Failed {
+            error: err.to_string(),
+            original_comment: entry.comment.clone(),
+        });
+        // 通信やサーバーのエラーでは掲示板に届いたか分からない
+        let maybe_posted = entry.posted_parts > 0
+            || matches!(
+                err,
+                BbsError::PartiallyPosted { .. }
+                    | BbsError::Network(_)
+                    | BbsError::HttpStatus { .. }
+                    | BbsError::ParseError { .. }
+            );
+        if !maybe_posted && self.comment().is_empty() {
+            self.set_comment(entry.comment.clone());
+            return SendError::Discard(err);
+        }
+        SendError::Keep(err)
+    }
+}
+
+/// URL は送信のタスクが次スレに移すので設定の `url` を正とする
 pub struct PopoverViewModel {
-    url: String,
-    comment: String,
-    comment_observer: CommentObserver,
-    sage: bool,
+    profile: PostingProfile,
+    profile_observer: ProfileObserver,
+    split: bool,
+    /// スレ立てに使うスレッドタイトル。残さない
+    subject: String,
+    outbox: Arc<Outbox>,
+    shared: Arc<Shared>,
 }
 
 impl PopoverViewModel {
     pub fn new() -> Self {
+        let path = if cfg!(test) {
+            None
+        } else {
+            config_dir().map(|x| x.join("settings.json"))
+        };
+        let settings = SettingsStore::load(path);
+        let saved = settings.get().clone();
+        for (board, policy) in &saved.unmappable_policies {
+            match Url::parse(board) {
+                Ok(board_url) => set_unmappable_policy(board_url, policy.clone()),
+                Err(err) => info!("invalid board url {}: {:?}", board, err),
+            }
+        }
+        let shared = Arc::new(Shared {
+            settings: Mutex::new(settings),
+            comment_observer: Mutex::new(None),
+            url_observer: Mutex::new(None),
+            url_completion_observer: Mutex::new(None),
+            post_state: Mutex::new(PostState::Idle),
+            post_state_observer: Mutex::new(None),
+            client: Mutex::new(None),
+        });
+        if let Err(err) = shared.client() {
+            warn!("invalid connection settings: {}", err);
+        }
         Self {
-            url: String::new(),
-            comment: String::new(),
-            comment_observer: None,
-            sage: true, // デフォルトでsageを有効にする
+            profile: saved.profile,
+            profile_observer: None,
+            split: saved.split,
+            subject: String::new(),
+            outbox: new_outbox(shared.clone()),
+            shared,
         }
     }
 
-    pub fn get_sage(&self) -> bool {
-        self.sage
+    /// 入力を設定に書き出す
+    fn save_settings(&mut self) {
+        self.shared.settings.lock().unwrap().update(|x| {
+            x.split = self.split;
+            x.profile.clone_from(&self.profile);
+        });
+    }
+
+    pub fn get_url(&self) -> String {
+        self.shared.settings.lock().unwrap().get().url.clone()
+    }
+
+    pub fn get_comment(&self) -> String {
+        self.shared.comment()
+    }
+
+    pub fn get_profile_state(&self) -> ProfileState {
+        ProfileState {
+            profile: self.profile.clone(),
+            name_preview: self.name_preview(),
+            labels: self
+                .shared
+                .settings
+                .lock()
+                .unwrap()
+                .get()
+                .profiles
+                .profiles
+                .iter()
+                .map(|x| x.label.clone())
+                .collect(),
+        }
     }
 
+    /// 板に既定のプロファイルがあれば切り替える
     pub fn set_url(&mut self, url: String) {
-        self.url = url;
+        let profile = {
+            let mut settings = self.shared.settings.lock().unwrap();
+            settings.update(|x| x.url.clone_from(&url));
+            settings.get().profiles.board_default(&url).cloned()
+        };
+        if let Some(profile) = profile.filter(|x| x.label != self.profile.label) {
+            info!("switching profile: {}", profile.label);
+            self.apply_profile(profile);
+        }
+        self.save_settings();
+        self.notify_url_completions();
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.profile.name = name;
+        self.save_settings();
+    }
+
+    /// 掲示板に表示される名前。トリップキーは `board_encoding` で計算する
+    pub fn name_preview(&self) -> String {
+        let encoding = board_encoding(self.shared.settings.lock().unwrap().get());
+        name_with_trip(encoding, &self.profile.name)
+    }
+
+    pub fn set_mail(&mut self, mail: String) {
+        self.profile.mail = mail;
+        self.save_settings();
     }
 
     pub fn set_comment(&mut self, comment: String) {
-        self.comment = comment.clone();
-        if let Some(observer) = self.comment_observer.as_ref() {
-            observer(comment);
-        }
+        self.shared.set_comment(comment);
     }
 
     pub fn set_sage(&mut self, sage: bool) {
-        self.sage = sage;
+        self.profile.sage = sage;
+        self.save_settings();
     }
 
+    pub fn get_split(&self) -> bool {
+        self.split
+    }
+
+    /// 長い本文を板の上限に合わせて分けて書き込むか
+    pub fn set_split(&mut self, split: bool) {
+        self.split = split;
+        self.save_settings();
+    }
+
+    pub fn set_subject(&mut self, subject: String) {
+        self.subject = subject;
+    }
+
+    /// 今の URL の板にスレッドを立てられるか
+    pub fn can_create_thread(&self) -> bool {
+        Url::parse(&self.get_url())
+            .ok()
+            .and_then(|x| capabilities(&x))
+            .is_some_and(|x| x.create_thread)
+    }
+
+    /// 次スレに移ったときに送信のタスクから呼ばれる
+    pub fn subscribe_url<F>(&mut self, observer: F)
+    where
+        F: Fn(String) + Send + Sync + 'static,
+    {
+        *self.shared.url_observer.lock().unwrap() = Some(Arc::new(observer));
+    }
+
+    /// 送れなかった本文を戻すときは送信のタスクからも呼ばれる
     pub fn subscribe_comment<F>(&mut self, observer: F)
     where
-        F: Fn(String) + Send + 'static,
+        F: Fn(String) + Send + Sync + 'static,
+    {
+        *self.shared.comment_observer.lock().unwrap() = Some(Arc::new(observer));
+    }
+
+    pub fn subscribe_profile<F>(&mut self, observer: F)
+    where
+        F: Fn(ProfileState) + Send + 'static,
+    {
+        self.profile_observer = Some(Box::new(observer));
+    }
+
+    fn apply_profile(&mut self, profile: PostingProfile) {
+        self.profile = profile;
+        self.save_settings();
+        if let Some(observer) = self.profile_observer.as_ref() {
+            observer(self.get_profile_state());
+        }
+    }
+
+    pub fn on_profile_selected(&mut self, label: &str) {
+        let profile = self
+            .shared
+            .settings
+            .lock()
+            .unwrap()
+            .get()
+            .profiles
+            .get(label)
+            .cloned();
+        if let Some(profile) = profile {
+            self.apply_profile(profile);
+        }
+    }
+
+    /// 今の名前・メール欄・sage を、名前から付けた名前のプロファイルとして残す
+    pub fn on_save_profile_clicked(&mut self) {
+        let mut profile = self.profile.clone();
+        profile.label = profile_label(&profile.name);
+        self.shared
+            .settings
+            .lock()
+            .unwrap()
+            .update(|x| x.profiles.upsert(profile.clone()));
+        self.apply_profile(profile);
+    }
+
+    /// 今のプロファイルを一覧から消す。名前などの入力はそのまま残す
+    pub fn on_delete_profile_clicked(&mut self) {
+        let label = self.profile.label.clone();
+        self.shared
+            .settings
+            .lock()
+            .unwrap()
+            .update(|x| x.profiles.remove(&label));
+        self.apply_profile(self.profile.clone());
+    }
+
+    /// 今のプロファイルをこの板の既定にする
+    pub fn on_make_board_default_clicked(&mut self) {
+        let profile = self.profile.clone();
+        let url = self.get_url();
+        self.shared.settings.lock().unwrap().update(|x| {
+            x.profiles.upsert(profile.clone());
+            if !x.profiles.set_board_default(&url, &profile.label) {
+                info!("not a bbs url: {}", url);
+            }
+        });
+        self.apply_profile(self.profile.clone());
+    }
+
+    pub fn get_url_completion_state(&self) -> UrlCompletionState {
+        let settings = self.shared.settings.lock().unwrap();
+        url_completion_state(settings.get(), &settings.get().url)
+    }
+
+    /// 候補やお気に入りが変わるたびに呼ぶ。送信のタスクからも呼ばれる
+    pub fn subscribe_url_completions<F>(&mut self, observer: F)
+    where
+        F: Fn(UrlCompletionState) + Send + Sync + 'static,
+    {
+        *self.shared.url_completion_observer.lock().unwrap() = Some(Arc::new(observer));
+    }
+
+    fn notify_url_completions(&self) {
+        let state = self.get_url_completion_state();
+        notify(&self.shared.url_completion_observer, state);
+    }
+
+    /// 候補を選んだら、その URL を入力にして返す
+    pub fn on_url_completion_selected(&mut self, index: usize) -> Option<String> {
+        let completion = self
+            .get_url_completion_state()
+            .completions
+            .get(index)?
+            .clone();
+        self.set_url(completion.url.clone());
+        Some(completion.url)
+    }
+
+    /// 今の URL をお気に入りに入れる。入っていれば外す
+    pub fn on_toggle_favourite_clicked(&mut self) {
+        let url = self.get_url();
+        self.shared.settings.lock().unwrap().update(|x| {
+            if x.is_favourite(&url) {
+                x.remove_favourite(&url);
+            } else {
+                x.add_favourite(&url);
+            }
+        });
+        self.notify_url_completions();
+    }
+
+    /// 送信待ちの状況が変わるたびに `outbox_status` の文字列を渡す
+    pub fn subscribe_outbox_status<F>(&mut self, observer: F)
+    where
+        F: Fn(String) + Send + Sync + 'static,
     {
-        self.comment_observer = Some(Box::new(observer));
+        observer(outbox_status(&self.outbox.entries()));
+        self.outbox
+            .subscribe(move |entries| observer(outbox_status(&entries)));
+    }
+
+    pub fn get_post_state(&self) -> PostState {
+        self.shared.post_state.lock().unwrap().clone()
+    }
+
+    /// 書き込みの状態が変わるたびに呼ぶ。送信のタスクから呼ばれる
+    pub fn subscribe_post_state<F>(&mut self, observer: F)
+    where
+        F: Fn(PostState) + Send + Sync + 'static,
+    {
+        *self.shared.post_state_observer.lock().unwrap() = Some(Arc::new(observer));
+    }
+
+    /// 名前・メール欄・本文のうち、板の文字コードで表せない文字
+    fn find_unmappable(&self, comment: &str) -> (Vec<Unmappable>, UnmappablePolicy) {
+        let settings = self.shared.settings.lock().unwrap();
+        let settings = settings.get();
+        let encoding = board_encoding(settings);
+        let policy = settings.unmappable_policy(&settings.url);
+        let characters = [self.profile.name.as_str(), &self.profile.email(), comment]
+            .iter()
+            .flat_map(|x| find_unmappable(encoding, x, &policy))
+            .collect();
+        (characters, policy)
     }
 
     pub fn on_post_clicked(&mut self) {
-        let url = self.url.clone();
-        let comment = self.comment.clone();
-        let sage = self.sage;
+        let state = self.get_post_state();
+        if state.is_busy() {
+            return;
+        }
+        let comment = self.get_comment();
+        let (characters, policy) = self.find_unmappable(&comment);
+        // 置き換える文字を見せた後にもう一度押されたら書き込む
+        let confirmed = matches!(
+            &state,
+            PostState::Unmappable { characters: x, refused: false } if *x == characters
+        );
+        if !characters.is_empty() && !confirmed {
+            self.shared.set_post_state(PostState::Unmappable {
+                characters,
+                refused: policy == UnmappablePolicy::Refuse,
+            });
+            return;
+        }
+        // 送る前に残しておき、アプリが落ちても失わないようにする
+        self.outbox.push(
+            self.get_url(),
+            self.profile.name.clone(),
+            self.profile.email(),
+            comment,
+            self.split,
+        );
         self.set_comment(String::new());
+    }
+
+    /// 今の URL の板に、本文を 1 レス目にしてスレッドを立てる。
+    /// 送信待ちには入れず、立てられなければ本文をそのまま残す
+    pub fn on_create_thread_clicked(&mut self) {
+        if self.get_post_state().is_busy() || self.subject.trim().is_empty() {
+            return;
+        }
+        let shared = self.shared.clone();
+        let url = self.get_url();
+        let subject = self.subject.clone();
+        let name = self.profile.name.clone();
+        let email = self.profile.email();
+        let comment = self.get_comment();
+        let charset = board_encoding(shared.settings.lock().unwrap().get())
+            .name()
+            .to_owned();
         spawn(async move {
-            let url: Url = url.parse()?;
-            let bbs_url = parse_bbs_url(url).map_err(|_| anyhow::anyhow!("Invalid BBS URL"))?;
-            let (thread_url, encoding, _title) = fetch_thread_url_encoding_name(&bbs_url).await?;
-            let bbs = bbs::new(&thread_url).await?;
+            shared.set_post_state(PostState::ResolvingThread);
+            let result = async {
+                let client = shared.client()?;
+                let board_url = Url::parse(&url).map_err(|_| BbsError::InvalidUrl(url.clone()))?;
+                let board = parse_bbs_url(&client, board_url)
+                    .map_err(|x| BbsError::InvalidUrl(x.to_string()))?
+                    .into_board();
+                shared.set_post_state(PostState::Posting);
+                board
+                    .create_thread(&charset, &subject, &name, &email, &comment)
+                    .await
+            }
+            .await;
+            match result {
+                Ok(thread_url) => {
+                    info!("created thread: {}", thread_url);
+                    shared.set_url(&thread_url);
+                    shared.record_post(&thread_url, &subject, &charset);
+                    shared.set_comment(String::new());
+                    shared.set_post_state(PostState::CreatedThread {
+                        thread_title: subject,
+                    });
+                }
+                Err(err) => shared.set_post_state(PostState::Failed {
+                    error: err.to_string(),
+                    original_comment: comment,
+                }),
+            }
+        });
+    }
 
-            let email = if sage { "sage" } else { "" };
-            bbs.post(&encoding, "", email, &comment).await?;
+    /// 止めている書き込みを送り直す
+    pub fn on_retry_clicked(&mut self) {
+        if let Some(head) = self.outbox.entries().first() {
+            self.outbox.retry(head.id);
+        }
+    }
 
-            Ok(())
-        });
+    /// 先頭の送信待ちを捨てる。送信中なら送信を止める
+    pub fn on_discard_clicked(&mut self) {
+        if let Some(head) = self.outbox.entries().first() {
+            self.outbox.discard(head.id);
+        }
     }
 }
+
+fn new_outbox(shared: Arc<Shared>) -> Arc<Outbox> {
+    let path = if cfg!(test) {
+        None
+    } else {
+        config_dir().map(|x| x.join("outbox.json"))
+    };
+    let outbox = Arc::new(Outbox::load(path));
+    // ワーカーが持つので弱い参照にする
+    let weak = Arc::downgrade(&outbox);
+    outbox.spawn_worker(OUTBOX_RETRY_INTERVAL, move |entry| {
+        let shared = shared.clone();
+        let outbox = weak.clone();
+        async move {
+            // ワーカーが動いている間は `Outbox` もある
+            let outbox = outbox.upgrade().unwrap();
+            shared.set_post_state(PostState::ResolvingThread);
+            let on_resolved = || shared.set_post_state(PostState::Posting);
+            let on_retry = |progress: RetryProgress| {
+                info!("retrying in {:?}: {:?}", progress.wait, progress.reason);
+                shared.set_post_state(PostState::Retrying {
+                    wait: progress.wait,
+                });
+            };
+            let result = match shared.client() {
+                Ok(client) => {
+                    let retry = shared.settings.lock().unwrap().get().retry.policy();
+                    send_entry(
+                        &client,
+                        &retry,
+                        &outbox,
+                        entry.clone(),
+                        &on_resolved,
+                        &on_retry,
+                    )
+                    .await
+                }
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(posted) => {
+                    if posted.moved {
+                        shared.follow_next_thread(&entry.url, &posted.url);
+                    }
+                    shared.record_post(&posted.url, &posted.title, &posted.charset);
+                    shared.set_post_state(PostState::Succeeded {
+                        thread_title: posted.title,
+                        moved: posted.moved,
+                    });
+                    Ok(())
+                }
+                Err(err) => Err(shared.fail(&entry, err)),
+            }
+        }
+    });
+    outbox
+}
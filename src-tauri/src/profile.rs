@@ -0,0 +1,95 @@
+#[cfg(test)]
+mod test;
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::bbs::board_url_and_key;
+
+/// 書き込むときの名前とメール欄
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostingProfile {
+    /// 一覧に出す名前
+    pub label: String,
+    /// 名前欄。`名前#キー` でトリップを付ける
+    pub name: String,
+    /// メール欄。sage とは別に `!id` などを書ける
+    pub mail: String,
+    pub sage: bool,
+}
+
+impl Default for PostingProfile {
+    fn default() -> Self {
+        Self {
+            label: "名無し".to_owned(),
+            name: String::new(),
+            mail: String::new(),
+            sage: true, // デフォルトでsageを有効にする
+        }
+    }
+}
+
+impl PostingProfile {
+    /// 送るメール欄。sage ならメール欄の先頭に付ける
+    pub fn email(&self) -> String {
+        let mail = self.mail.trim();
+        if !self.sage || mail.split_whitespace().any(|x| x == "sage") {
+            return mail.to_owned();
+        }
+        if mail.is_empty() {
+            "sage".to_owned()
+        } else {
+            format!("sage {}", mail)
+        }
+    }
+}
+
+/// スレッドや板の URL から板を表す文字列を作る。掲示板の URL でなければ `None`
+pub fn board_key(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    Some(board_url_and_key(&url)?.0.to_string())
+}
+
+/// 書き込みプロファイルと、板ごとに使うプロファイル
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profiles {
+    pub profiles: Vec<PostingProfile>,
+    /// `board_key` → プロファイルの `label`
+    pub board_defaults: BTreeMap<String, String>,
+}
+
+impl Profiles {
+    pub fn get(&self, label: &str) -> Option<&PostingProfile> {
+        self.profiles.iter().find(|x| x.label == label)
+    }
+
+    /// 同じ `label` があれば置き換える
+    pub fn upsert(&mut self, profile: PostingProfile) {
+        match self.profiles.iter_mut().find(|x| x.label == profile.label) {
+            Some(x) => *x = profile,
+            None => self.profiles.push(profile),
+        }
+    }
+
+    pub fn remove(&mut self, label: &str) {
+        self.profiles.retain(|x| x.label != label);
+        self.board_defaults.retain(|_, x| x != label);
+    }
+
+    /// `url` の板で `label` のプロファイルを使う
+    pub fn set_board_default(&mut self, url: &str, label: &str) -> bool {
+        let Some(key) = board_key(url) else {
+            return false;
+        };
+        self.board_defaults.insert(key, label.to_owned());
+        true
+    }
+
+    /// `url` の板で使うプロファイル
+    pub fn board_default(&self, url: &str) -> Option<&PostingProfile> {
+        let label = self.board_defaults.get(&board_key(url)?)?;
+        self.get(label)
+    }
+}
@@ -0,0 +1,46 @@
+use super::{PostingProfile, Profiles, board_key};
+
+#[test]
+fn test_email() {
+    let profile = |mail: &str, sage: bool| PostingProfile {
+        mail: mail.to_owned(),
+        sage,
+        ..Default::default()
+    };
+    assert_eq!(profile("", true).email(), "sage");
+    assert_eq!(profile("", false).email(), "");
+    assert_eq!(profile("!id", true).email(), "sage !id");
+    assert_eq!(profile("!id", false).email(), "!id");
+    assert_eq!(profile(" sage ", true).email(), "sage");
+}
+
+#[test]
+fn test_board_defaults() {
+    let thread = "https://example.com/test/read.cgi/contact/1700000000/l50";
+    assert_eq!(
+        board_key(thread).as_deref(),
+        Some("https://example.com/contact/")
+    );
+    assert_eq!(board_key("not a url"), None);
+
+    let mut profiles = Profiles::default();
+    let streamer = PostingProfile {
+        label: "配信者".to_owned(),
+        name: "配信者#key".to_owned(),
+        mail: String::new(),
+        sage: false,
+    };
+    profiles.upsert(PostingProfile::default());
+    profiles.upsert(streamer.clone());
+    assert!(profiles.set_board_default(thread, "配信者"));
+
+    // 同じ板なら別のスレッドでも選ばれる
+    let other = "https://example.com/test/read.cgi/contact/1700100000/";
+    assert_eq!(profiles.board_default(other), Some(&streamer));
+    let other_board = "https://example.com/test/read.cgi/other/1700100000/";
+    assert_eq!(profiles.board_default(other_board), None);
+
+    profiles.remove("配信者");
+    assert_eq!(profiles.board_default(other), None);
+    assert_eq!(profiles.profiles, [PostingProfile::default()]);
+}
@@ -0,0 +1,313 @@
+#[cfg(test)]
+mod test;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+use url::Url;
+
+use crate::{
+    app_dir::JsonSaver,
+    bbs::{BbsClientConfig, RetryPolicy, UnmappablePolicy, board_url_and_key},
+    profile::{PostingProfile, Profiles, board_key},
+};
+
+/// 書き込んだスレッドをいくつまで覚えるか
+const MAX_RECENT_THREADS: usize = 20;
+/// 入力が続く間は書き出さずに待つ時間
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// 今の設定ファイルの版。形を変えたら上げて `migrate` で読み替える
+pub const VERSION: u32 = 1;
+
+/// 再起動しても残す設定と入力
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    pub url: String,
+    /// 書きかけの本文
+    pub draft: String,
+    pub split: bool,
+    /// 選んでいるプロファイル。一覧に保存していない変更も含む
+    pub profile: PostingProfile,
+    pub profiles: Profiles,
+    /// 書き込んだスレッド。新しい順
+    pub recent_threads: Vec<RecentThread>,
+    pub favourite_threads: Vec<FavouriteThread>,
+    /// `board_key` → 書き込めたときの文字コード
+    pub board_charsets: BTreeMap<String, String>,
+    /// `board_key` → 板の文字コードで表せない文字の扱い
+    pub unmappable_policies: BTreeMap<String, UnmappablePolicy>,
+    pub connection: ConnectionSettings,
+    pub retry: RetrySettings,
+}
+
+/// 掲示板との通信の設定
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectionSettings {
+    /// 空なら既定の User-Agent
+    pub user_agent: String,
+    pub connect_timeout_secs: u64,
+    pub read_timeout_secs: u64,
+    /// 追いかけるリダイレクトの回数。0 なら追いかけない
+    pub max_redirects: usize,
+    /// `http://host:port` や `socks5://host:port`。空なら使わない
+    pub proxy: String,
+}
+
+impl Default for ConnectionSettings {
+    fn default() -> Self {
+        let config = BbsClientConfig::default();
+        Self {
+            user_agent: String::new(),
+            connect_timeout_secs: config.connect_timeout.as_secs(),
+            read_timeout_secs: config.read_timeout.as_secs(),
+            max_redirects: config.max_redirects,
+            proxy: String::new(),
+        }
+    }
+}
+
+/// 連投規制やサーバーエラーで書き込めなかったときの再試行の設定
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetrySettings {
+    /// 0 なら再試行しない
+    pub max_retries: u32,
+    pub initial_backoff_secs: u64,
+    pub max_backoff_secs: u64,
+    /// 待ち時間の合計の上限
+    pub budget_secs: u64,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        let policy = RetryPolicy::default();
+        Self {
+            max_retries: policy.max_retries,
+            initial_backoff_secs: policy.initial_backoff.as_secs(),
+            max_backoff_secs: policy.max_backoff.as_secs(),
+            budget_secs: policy.budget.as_secs(),
+        }
+    }
+}
+
+impl RetrySettings {
+    pub fn policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: self.max_retries,
+            initial_backoff: Duration::from_secs(self.initial_backoff_secs),
+            max_backoff: Duration::from_secs(self.max_backoff_secs),
+            budget: Duration::from_secs(self.budget_secs),
+        }
+    }
+}
+
+impl ConnectionSettings {
+    pub fn client_config(&self) -> BbsClientConfig {
+        let default = BbsClientConfig::default();
+        BbsClientConfig {
+            user_agent: Some(self.user_agent.trim())
+                .filter(|x| !x.is_empty())
+                .map_or(default.user_agent, str::to_owned),
+            connect_timeout: Duration::from_secs(self.connect_timeout_secs),
+            read_timeout: Duration::from_secs(self.read_timeout_secs),
+            max_redirects: self.max_redirects,
+            proxy: Some(self.proxy.trim())
+                .filter(|x| !x.is_empty())
+                .map(str::to_owned),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentThread {
+    pub url: String,
+    /// 分からなければ空
+    pub title: String,
+    /// `board_key`。掲示板の URL でなければ空
+    pub board: String,
+    /// 最後に書き込んだ時刻 (UNIX 時間の秒)。分からなければ 0
+    pub last_posted_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FavouriteThread {
+    pub url: String,
+    pub title: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            url: String::new(),
+            draft: String::new(),
+            split: false,
+            profile: PostingProfile::default(),
+            profiles: Profiles::default(),
+            recent_threads: Vec::new(),
+            favourite_threads: Vec::new(),
+            board_charsets: BTreeMap::new(),
+            unmappable_policies: BTreeMap::new(),
+            connection: ConnectionSettings::default(),
+            retry: RetrySettings::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// 書き込んだスレッドを先頭に置く
+    pub fn record_post(&mut self, url: &str, title: &str, last_posted_at: u64) {
+        self.recent_threads.retain(|x| !same_thread(&x.url, url));
+        self.recent_threads.insert(
+            0,
+            RecentThread {
+                url: url.to_owned(),
+                title: title.to_owned(),
+                board: board_key(url).unwrap_or_default(),
+                last_posted_at,
+            },
+        );
+        self.recent_threads.truncate(MAX_RECENT_THREADS);
+        // お気に入りのタイトルも新しくする
+        for favourite in self
+            .favourite_threads
+            .iter_mut()
+            .filter(|x| same_thread(&x.url, url))
+        {
+            favourite.title = title.to_owned();
+        }
+    }
+
+    pub fn is_favourite(&self, url: &str) -> bool {
+        self.favourite_threads
+            .iter()
+            .any(|x| same_thread(&x.url, url))
+    }
+
+    /// お気に入りに入れる。タイトルは書き込んだときに分かったものを使う
+    pub fn add_favourite(&mut self, url: &str) {
+        if url.is_empty() || self.is_favourite(url) {
+            return;
+        }
+        let title = self
+            .recent_threads
+            .iter()
+            .find(|x| same_thread(&x.url, url))
+            .map(|x| x.title.clone())
+            .unwrap_or_default();
+        self.favourite_threads.push(FavouriteThread {
+            url: url.to_owned(),
+            title,
+        });
+    }
+
+    pub fn remove_favourite(&mut self, url: &str) {
+        self.favourite_threads.retain(|x| !same_thread(&x.url, url));
+    }
+
+    /// 板に書き込めたときの文字コード。書き込んだことがなければ `None`
+    pub fn board_charset(&self, url: &str) -> Option<&str> {
+        self.board_charsets
+            .get(&board_key(url)?)
+            .map(String::as_str)
+    }
+
+    pub fn set_board_charset(&mut self, url: &str, charset: &str) {
+        if let Some(board) = board_key(url) {
+            self.board_charsets.insert(board, charset.to_owned());
+        }
+    }
+
+    pub fn unmappable_policy(&self, url: &str) -> UnmappablePolicy {
+        board_key(url)
+            .and_then(|x| self.unmappable_policies.get(&x).cloned())
+            .unwrap_or_default()
+    }
+}
+
+/// `…/l50` のような表示範囲の違いを除いて同じスレッドや板か。掲示板の URL でなければ文字列で比べる
+fn same_thread(a: &str, b: &str) -> bool {
+    let identity = |url: &str| board_url_and_key(&Url::parse(url).ok()?);
+    match (identity(a), identity(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// 古い版の設定を今の版にする
+fn migrate(mut value: Value) -> anyhow::Result<Settings> {
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version > VERSION as u64 {
+        bail!("settings version {} is newer than {}", version, VERSION);
+    }
+    value["version"] = VERSION.into();
+    Ok(serde_json::from_value(value)?)
+}
+
+/// 設定ファイルを読む。無ければ `None`
+fn read_document(path: &Path) -> anyhow::Result<Option<Value>> {
+    let Ok(bytes) = fs::read(path) else {
+        return Ok(None);
+    };
+    Ok(Some(serde_json::from_slice(&bytes)?))
+}
+
+/// 設定を版付きの JSON ファイルに残す。入力のたびに呼ばれるので書き出しは別のスレッドでまとめて行う
+pub struct SettingsStore {
+    settings: Settings,
+    /// 読めなかったファイルや新しい版のファイルは上書きしないので作らない
+    saver: Option<JsonSaver<Settings>>,
+}
+
+impl SettingsStore {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let document = path.as_deref().map(read_document).transpose();
+        let (settings, read_only) = match document.map(Option::flatten) {
+            Ok(None) => (Settings::default(), false),
+            Ok(Some(value)) => match migrate(value) {
+                Ok(settings) => (settings, false),
+                Err(err) => {
+                    warn!("failed to migrate settings: {:?}", err);
+                    (Settings::default(), true)
+                }
+            },
+            Err(err) => {
+                warn!("broken settings: {:?}", err);
+                (Settings::default(), true)
+            }
+        };
+        Self {
+            settings,
+            saver: path
+                .filter(|_| !read_only)
+                .map(|x| JsonSaver::spawn(x, SAVE_DELAY)),
+        }
+    }
+
+    pub fn get(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// 変更してファイルに書き出す
+    pub fn update(&mut self, f: impl FnOnce(&mut Settings)) {
+        let before = self.settings.clone();
+        f(&mut self.settings);
+        if self.settings != before
+            && let Some(saver) = &self.saver
+        {
+            saver.save(self.settings.clone());
+        }
+    }
+}
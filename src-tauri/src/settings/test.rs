@@ -0,0 +1,168 @@
+use std::{fs, path::PathBuf};
+
+use crate::bbs::{BbsClientConfig, RetryPolicy, UnmappablePolicy};
+
+use super::{
+    ConnectionSettings, FavouriteThread, RecentThread, RetrySettings, Settings, SettingsStore,
+    VERSION,
+};
+
+/// テストごとの空のディレクトリ
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("settings-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_settings_store() {
+    let dir = temp_dir("store");
+    let path = dir.join("settings.json");
+
+    let mut store = SettingsStore::load(Some(path.clone()));
+    assert_eq!(*store.get(), Settings::default());
+    assert!(store.get().profile.sage);
+    store.update(|x| {
+        x.url = "https://example.com/test/read.cgi/board/1/".to_owned();
+        x.draft = "書きかけ".to_owned();
+        x.profile.sage = false;
+        x.record_post("https://example.com/test/read.cgi/board/1/", "", 1);
+        x.record_post("https://example.com/test/read.cgi/board/2/", "二", 2);
+        x.record_post("https://example.com/test/read.cgi/board/1/", "一", 3);
+        x.add_favourite("https://example.com/test/read.cgi/board/2/");
+    });
+    let recent = &store.get().recent_threads;
+    assert_eq!(recent.len(), 2);
+    assert_eq!(
+        recent[0],
+        RecentThread {
+            url: "https://example.com/test/read.cgi/board/1/".to_owned(),
+            title: "一".to_owned(),
+            board: "https://example.com/board/".to_owned(),
+            last_posted_at: 3,
+        }
+    );
+    assert_eq!(recent[1].last_posted_at, 2);
+    assert_eq!(
+        store.get().favourite_threads,
+        [FavouriteThread {
+            url: "https://example.com/test/read.cgi/board/2/".to_owned(),
+            title: "二".to_owned(),
+        }]
+    );
+
+    // 書き込むとお気に入りのタイトルも新しくなる
+    store.update(|x| x.record_post("https://example.com/test/read.cgi/board/2/", "二改", 4));
+    assert_eq!(store.get().favourite_threads[0].title, "二改");
+    // 表示範囲が違っても同じスレッドとみなす
+    let l50 = "https://example.com/test/read.cgi/board/2/l50";
+    assert!(store.get().is_favourite(l50));
+    assert!(
+        !store
+            .get()
+            .is_favourite("https://example.com/test/read.cgi/board/3/")
+    );
+    store.update(|x| x.add_favourite(l50));
+    assert_eq!(store.get().favourite_threads.len(), 1);
+    store.update(|x| x.remove_favourite(l50));
+    assert!(store.get().favourite_threads.is_empty());
+    // 表示範囲が違っても履歴には一つだけ残す
+    store.update(|x| x.record_post(l50, "二", 5));
+    let recent = &store.get().recent_threads;
+    assert_eq!(recent.len(), 2);
+    assert_eq!(recent[0].url, l50);
+
+    // 書き出しを待つ
+    let saved = store.get().clone();
+    drop(store);
+    let reloaded = SettingsStore::load(Some(path.clone()));
+    assert_eq!(*reloaded.get(), saved);
+    assert_eq!(reloaded.get().version, VERSION);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_connection_settings() {
+    let connection = ConnectionSettings::default();
+    assert_eq!(connection.client_config(), BbsClientConfig::default());
+
+    let connection: ConnectionSettings = serde_json::from_str(
+        r#"{ "user_agent": " test/1.0 ", "proxy": "socks5://127.0.0.1:1080" }"#,
+    )
+    .unwrap();
+    let config = connection.client_config();
+    assert_eq!(config.user_agent, "test/1.0");
+    assert_eq!(config.proxy.as_deref(), Some("socks5://127.0.0.1:1080"));
+    assert_eq!(config.read_timeout, BbsClientConfig::default().read_timeout);
+
+    assert_eq!(RetrySettings::default().policy(), RetryPolicy::default());
+    let retry: RetrySettings = serde_json::from_str(r#"{ "max_retries": 0 }"#).unwrap();
+    assert_eq!(retry.policy().max_retries, 0);
+}
+
+#[test]
+fn test_board_charset_and_unmappable_policy() {
+    let dir = temp_dir("board");
+    let path = dir.join("settings.json");
+    fs::write(
+        &path,
+        r#"{
+            "version": 1,
+            "unmappable_policies": {
+                "https://example.com/board/": { "Substitute": { "😂": "(笑)" } },
+                "https://example.com/strict/": "Refuse"
+            }
+        }"#,
+    )
+    .unwrap();
+
+    let mut store = SettingsStore::load(Some(path.clone()));
+    let thread = "https://example.com/test/read.cgi/board/1/";
+    assert_eq!(store.get().board_charset(thread), None);
+    store.update(|x| x.set_board_charset(thread, "euc-jp"));
+    // 同じ板の別のスレッドにも使う
+    assert_eq!(
+        store
+            .get()
+            .board_charset("https://example.com/test/read.cgi/board/2/"),
+        Some("euc-jp")
+    );
+    assert_eq!(
+        store.get().unmappable_policy(thread),
+        UnmappablePolicy::Substitute([("😂".to_owned(), "(笑)".to_owned())].into())
+    );
+    assert_eq!(
+        store
+            .get()
+            .unmappable_policy("https://example.com/test/read.cgi/strict/1/"),
+        UnmappablePolicy::Refuse
+    );
+    assert_eq!(
+        store.get().unmappable_policy("https://example.com/other/"),
+        UnmappablePolicy::NumericReference
+    );
+    let saved = store.get().clone();
+    drop(store);
+    assert_eq!(*SettingsStore::load(Some(path.clone())).get(), saved);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_newer_settings_version() {
+    let dir = temp_dir("newer");
+    let path = dir.join("settings.json");
+
+    // 新しい版のファイルは読まずに残す
+    let newer = format!(r#"{{ "version": {}, "url": "x" }}"#, VERSION + 1);
+    fs::write(&path, &newer).unwrap();
+    let mut store = SettingsStore::load(Some(path.clone()));
+    assert_eq!(*store.get(), Settings::default());
+    store.update(|x| x.url = "y".to_owned());
+    drop(store);
+    assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+
+    fs::remove_dir_all(&dir).unwrap();
+}